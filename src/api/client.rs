@@ -1,31 +1,142 @@
 use crate::api::structs::*;
+use crate::api::ids::{AlbumId, ArtistId, PlaylistId, TrackId, UserLogin};
+use crate::utils;
 
 use std::collections::HashMap;
 use std::error::Error;
-use std::time::{SystemTime, SystemTimeError, UNIX_EPOCH};
+use std::fmt;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime, SystemTimeError, UNIX_EPOCH};
 use sha2::Sha256;
 use base64::engine::{general_purpose, Engine};
 use hmac::{Hmac, Mac};
 use hmac::digest::crypto_common::InvalidLength as CryptoInvalidLength;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
-use reqwest::blocking::{Client, Response as ReqwestResp};
-use reqwest::Error as ReqwestErr;
-use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT, AUTHORIZATION, RANGE};
+use reqwest::blocking::{Client, RequestBuilder, Response as ReqwestResp};
+use reqwest::header::{HeaderMap, HeaderValue, USER_AGENT, AUTHORIZATION, CONTENT_RANGE, RANGE, RETRY_AFTER};
+use reqwest::StatusCode;
 
 const BASE_URL: &str = "https://api.music.yandex.net";
 const SECRET: &str = "kzqU4XhfCaY6B6JTHODeq5";
 const YANDEX_USER_AGENT: &str = "YandexMusicDesktopAppWindows/5.23.2";
+const BASE_BACKOFF_MS: u64 = 500;
+const MAX_BACKOFF_MS: u64 = 30_000;
+const CACHE_FILENAME: &str = "response_cache.json";
+// get_file_info responses carry a signed, time-limited download URL, so they're capped to a
+// short TTL regardless of what the cache was configured with, to avoid handing out a URL
+// that's already expired by the time it's read back out of the cache.
+const FILE_INFO_CACHE_TTL_CAP: Duration = Duration::from_secs(60);
 
 type HmacSha256 = Hmac<Sha256>;
 
+// Distinguishes a captcha challenge from every other failure mode, so the top URL loop in
+// main.rs can downcast, print an actionable message, and stop hammering the API instead of
+// just surfacing an opaque deserialization error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClientError {
+    Captcha,
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Captcha => write!(
+                f,
+                "Yandex returned a captcha challenge - this IP is temporarily flagged. \
+                 Increase the sleep interval, or solve the captcha in a browser and re-run \
+                 with exported cookies."
+            ),
+        }
+    }
+}
+
+impl Error for ClientError {}
+
+// Yandex returns a captcha challenge as a normal 200 JSON body instead of an HTTP error, so
+// every response is routed through here before being deserialized into its real type: parse
+// to a raw Value first, check for the captcha shape, then decode the Value into T.
+fn parse_response<T: DeserializeOwned>(resp: ReqwestResp) -> Result<T, Box<dyn Error>> {
+    let value: serde_json::Value = resp.json()?;
+
+    if let Some(obj) = value.as_object() {
+        let is_captcha = obj.get("type").and_then(|t| t.as_str()) == Some("captcha")
+            || obj.contains_key("captcha");
+        if is_captcha {
+            return Err(Box::new(ClientError::Captcha));
+        }
+    }
+
+    Ok(serde_json::from_value(value)?)
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+struct CachedResponse {
+    cached_at: u64,
+    value: serde_json::Value,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct ResponseCache {
+    #[serde(default)]
+    entries: HashMap<String, CachedResponse>,
+}
+
+#[derive(Clone)]
 pub struct YandexMusicClient {
     c: Client,
     pub login: String,
     token: String,
+    max_retries: u32,
+    cache: Option<Arc<Mutex<ResponseCache>>>,
+    cache_path: Option<PathBuf>,
+    cache_ttl: Duration,
+}
+
+// Exponential backoff (base 500ms, doubling, capped at 30s) plus up to 250ms of jitter so a
+// batch of concurrent workers hitting a 5xx at the same time don't all retry in lockstep.
+fn backoff_delay(attempt: u32) -> Duration {
+    let exp = BASE_BACKOFF_MS.saturating_mul(1u64 << attempt.min(16)).min(MAX_BACKOFF_MS);
+    let jitter = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_millis() as u64 % 250)
+        .unwrap_or(0);
+    Duration::from_millis(exp + jitter)
+}
+
+// Splits `codes` into 2-char chunks (the packed two-letter-ISO-code format these
+// restriction lists use) and checks whether `country` is one of them.
+fn country_contained(codes: &str, country: &str) -> bool {
+    codes.as_bytes().chunks(2).any(|chunk| chunk.eq_ignore_ascii_case(country.as_bytes()))
+}
+
+// Never print a raw token; only enough of it to tell two lines in the file apart.
+fn mask_token(token: &str) -> String {
+    if token.len() <= 8 {
+        "*".repeat(token.len())
+    } else {
+        format!("{}...{}", &token[..4], &token[token.len() - 4..])
+    }
+}
+
+// Per-line outcome of `YandexMusicClient::from_token_file`, keyed by login where the token
+// was valid enough to sign in, or by a masked token where it wasn't.
+#[derive(Default)]
+pub struct TokenReport {
+    pub valid: Vec<String>,
+    pub non_plus: Vec<String>,
+    pub invalid: Vec<String>,
 }
 
 impl YandexMusicClient {
-    pub fn new(token: &str) -> Result<YandexMusicClient, Box<dyn Error>> {
+    // Builds and signs in a client without rejecting non-Plus accounts, so callers that want
+    // to try several tokens (see `from_token_file`) can inspect `has_plus` themselves.
+    fn authenticate(token: &str) -> Result<(YandexMusicClient, bool), Box<dyn Error>> {
         let mut headers = HeaderMap::new();
         headers.insert(USER_AGENT, HeaderValue::from_static(YANDEX_USER_AGENT));
 
@@ -38,37 +149,207 @@ impl YandexMusicClient {
             c,
             token: format!("OAuth {}", token),
             login: String::new(),
+            max_retries: 5,
+            cache: None,
+            cache_path: None,
+            cache_ttl: Duration::default(),
         };
 
         let user_info = yandex_client.get_user_info()?;
-        if !user_info.has_plus {
+        yandex_client.login = user_info.login;
+        Ok((yandex_client, user_info.has_plus))
+    }
+
+    pub fn new(token: &str) -> Result<YandexMusicClient, Box<dyn Error>> {
+        let (yandex_client, has_plus) = Self::authenticate(token)?;
+        if !has_plus {
             return Err("active plus subscription required".into());
         }
-
-        yandex_client.login = user_info.login;
         Ok(yandex_client)
     }
 
-    pub fn get_user_info(&mut self) -> Result<UserInfoResult, ReqwestErr> {
+    // Reads one token per line from `path` (blank lines ignored), validates each via
+    // `get_user_info`, and returns the first account with an active Plus subscription
+    // alongside a report of how every line checked out, so callers juggling several accounts
+    // don't have to test tokens by hand.
+    pub fn from_token_file(path: &Path) -> Result<(YandexMusicClient, TokenReport), Box<dyn Error>> {
+        let data = fs::read_to_string(path)?;
+        let tokens: Vec<&str> = data.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+        if tokens.is_empty() {
+            return Err("token file has no tokens in it".into());
+        }
+
+        let mut report = TokenReport::default();
+        let mut chosen: Option<YandexMusicClient> = None;
+
+        for token in tokens {
+            match Self::authenticate(token) {
+                Ok((client, true)) => {
+                    report.valid.push(client.login.clone());
+                    if chosen.is_none() {
+                        chosen = Some(client);
+                    }
+                },
+                Ok((client, false)) => report.non_plus.push(client.login.clone()),
+                Err(_) => report.invalid.push(mask_token(token)),
+            }
+        }
+
+        let client = chosen.ok_or("none of the tokens in the file have an active Plus subscription")?;
+        Ok((client, report))
+    }
+
+    // Routes a request through retry handling: 429 sleeps for the server-given Retry-After,
+    // 5xx and connection/timeout errors get exponential backoff, both bounded by
+    // `max_retries`. Takes a closure instead of a built `RequestBuilder` since a
+    // `RequestBuilder` is consumed by `send()` and so can't be reused across attempts.
+    fn send_with_retry(&self, build: impl Fn() -> RequestBuilder) -> Result<ReqwestResp, Box<dyn Error>> {
+        let mut attempt = 0;
+        loop {
+            match build().send() {
+                Ok(resp) if resp.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    if attempt >= self.max_retries {
+                        resp.error_for_status_ref()?;
+                        unreachable!();
+                    }
+                    let wait = resp.headers().get(RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.parse::<u64>().ok())
+                        .unwrap_or(1);
+                    thread::sleep(Duration::from_secs(wait));
+                    attempt += 1;
+                },
+                Ok(resp) if resp.status().is_server_error() => {
+                    if attempt >= self.max_retries {
+                        resp.error_for_status_ref()?;
+                        unreachable!();
+                    }
+                    thread::sleep(backoff_delay(attempt));
+                    attempt += 1;
+                },
+                Ok(resp) => {
+                    resp.error_for_status_ref()?;
+                    return Ok(resp);
+                },
+                Err(e) if (e.is_timeout() || e.is_connect()) && attempt < self.max_retries => {
+                    thread::sleep(backoff_delay(attempt));
+                    attempt += 1;
+                },
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    // Modeled on librespot's restriction check: allowed-list present -> country must be in
+    // it; forbidden-list present -> country must not be in it; neither -> allowed by
+    // default. `country` defaults to the account's region (from `get_user_info`) when None.
+    pub fn is_track_available<T: Restricted>(&mut self, track: &T, country: Option<&str>) -> Result<bool, Box<dyn Error>> {
+        let country = match country {
+            Some(c) => c.to_string(),
+            None => self.get_user_info()?.region.unwrap_or_default(),
+        };
+
+        if country.is_empty() {
+            return Ok(true);
+        }
+
+        if let Some(allowed) = track.allowed_countries() {
+            if !country_contained(allowed, &country) {
+                return Ok(false);
+            }
+        }
+
+        if let Some(forbidden) = track.forbidden_countries() {
+            if country_contained(forbidden, &country) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn get_user_info(&mut self) -> Result<UserInfoResult, Box<dyn Error>> {
         let url = format!("{}/account/about", BASE_URL);
-        let resp = self.c.get(url)
-            .header(AUTHORIZATION, &self.token)
-            .send()?;
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
+            .header(AUTHORIZATION, &self.token))?;
 
-        resp.error_for_status_ref()?;
-        let meta: UserInfo = resp.json()?;
+        let meta: UserInfo = parse_response(resp)?;
         Ok(meta.result)
     }
 
-    pub fn get_album_meta(&mut self, album_id: &str) -> Result<AlbumResult, ReqwestErr> {
+    // Enables the on-disk response cache: subsequent get_album_meta/get_artist_meta/
+    // get_file_info calls are looked up in `dir`/response_cache.json under `ttl` before
+    // hitting the network. Chain after `new()`, mirroring MusicBrainzClient's
+    // load-on-construct cache.
+    pub fn with_cache(mut self, dir: PathBuf, ttl: Duration) -> Result<YandexMusicClient, Box<dyn Error>> {
+        fs::create_dir_all(&dir)?;
+        let cache_path = dir.join(CACHE_FILENAME);
+
+        let cache: ResponseCache = if cache_path.exists() {
+            let data = fs::read_to_string(&cache_path)?;
+            serde_json::from_str(&data)?
+        } else {
+            ResponseCache::default()
+        };
+
+        self.cache = Some(Arc::new(Mutex::new(cache)));
+        self.cache_path = Some(cache_path);
+        self.cache_ttl = ttl;
+        Ok(self)
+    }
+
+    // Drops every cached response, both in memory and on disk. A no-op if caching isn't enabled.
+    pub fn clear_cache(&self) -> Result<(), Box<dyn Error>> {
+        if let (Some(cache), Some(path)) = (&self.cache, &self.cache_path) {
+            cache.lock().unwrap().entries.clear();
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn cache_get<T: DeserializeOwned>(&self, key: &str, ttl: Duration) -> Option<T> {
+        let cache = self.cache.as_ref()?;
+        let entries = &cache.lock().unwrap().entries;
+        let entry = entries.get(key)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        if now.saturating_sub(entry.cached_at) > ttl.as_secs() {
+            return None;
+        }
+
+        serde_json::from_value(entry.value.clone()).ok()
+    }
+
+    fn cache_set<T: Serialize>(&self, key: &str, value: &T) -> Result<(), Box<dyn Error>> {
+        let (cache, path) = match (&self.cache, &self.cache_path) {
+            (Some(cache), Some(path)) => (cache, path),
+            _ => return Ok(()),
+        };
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut guard = cache.lock().unwrap();
+        guard.entries.insert(key.to_string(), CachedResponse { cached_at: now, value: serde_json::to_value(value)? });
+
+        let data = serde_json::to_string_pretty(&*guard)?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    pub fn get_album_meta(&mut self, album_id: &AlbumId) -> Result<AlbumResult, Box<dyn Error>> {
+        let key = format!("album:{}", album_id);
+        if let Some(cached) = self.cache_get(&key, self.cache_ttl) {
+            return Ok(cached);
+        }
+
         let url = format!("{}/albums/{}/with-tracks", BASE_URL, album_id);
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             // Auth header not needed, but the Win app does send one.
-            .header(AUTHORIZATION, &self.token)
-            .send()?;
+            .header(AUTHORIZATION, &self.token))?;
 
-        resp.error_for_status_ref()?;
-        let meta: AlbumMeta = resp.json()?;
+        let meta: AlbumMeta = parse_response(resp)?;
+        self.cache_set(&key, &meta.result)?;
         Ok(meta.result)
     }
 
@@ -82,7 +363,7 @@ impl YandexMusicClient {
         }
     }
 
-    fn create_lyrics_signature(&mut self, ts: &str, track_id: &str) -> Result<String, CryptoInvalidLength> {
+    fn create_lyrics_signature(&mut self, ts: &str, track_id: &TrackId) -> Result<String, CryptoInvalidLength> {
         let msg = format!("{}{}", track_id, ts);
 
         let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes())?;
@@ -95,7 +376,7 @@ impl YandexMusicClient {
         Ok(base64_encoded)
     }
 
-    pub fn get_lyrics_meta(&mut self, track_id: &str, timed: bool) -> Result<LyricsResult, Box<dyn Error>> {
+    pub fn get_lyrics_meta(&mut self, track_id: &TrackId, timed: bool) -> Result<LyricsResult, Box<dyn Error>> {
         let url = format!("{}/tracks/{}/lyrics", BASE_URL, track_id);
         let ts = self.get_unix_timestamp()?;
 
@@ -104,24 +385,22 @@ impl YandexMusicClient {
 
         let params: HashMap<&str, &str> = HashMap::from([
             ("timeStamp", ts.as_str()),
-            ("trackId", track_id),
+            ("trackId", track_id.as_str()),
             ("format", format),
             ("sign", &signature),
         ]);
 
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             .header(AUTHORIZATION, &self.token)
             .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
-            .query(&params)
-            .send()?;
+            .query(&params))?;
 
-        resp.error_for_status_ref()?;
-        let meta: LyricsMeta = resp.json()?;
+        let meta: LyricsMeta = parse_response(resp)?;
         Ok(meta.result)
     }
 
     // :)
-    fn create_signature(&mut self, ts: &str, track_id: &str, quality: &str) -> Result<String, CryptoInvalidLength> {
+    fn create_signature(&mut self, ts: &str, track_id: &TrackId, quality: &str) -> Result<String, CryptoInvalidLength> {
         let msg = format!("{}{}{}flacaache-aacmp3raw", ts, track_id, quality);
 
         let mut mac = HmacSha256::new_from_slice(SECRET.as_bytes())?;
@@ -134,7 +413,15 @@ impl YandexMusicClient {
         Ok(base64_encoded[..base64_encoded.len() - 1].to_string())
     }
 
-    pub fn get_file_info(&mut self, track_id: &str, quality: &str) -> Result<DownloadInfo, Box<dyn Error>> {
+    pub fn get_file_info(&mut self, track_id: &TrackId, quality: &str) -> Result<DownloadInfo, Box<dyn Error>> {
+        let key = format!("file_info:{}:{}", track_id, quality);
+        // Capped to FILE_INFO_CACHE_TTL_CAP regardless of the configured TTL, since the
+        // cached value's download URL is signed and time-limited.
+        let ttl = self.cache_ttl.min(FILE_INFO_CACHE_TTL_CAP);
+        if let Some(cached) = self.cache_get(&key, ttl) {
+            return Ok(cached);
+        }
+
         let url = format!("{}/get-file-info", BASE_URL);
 
         let ts = self.get_unix_timestamp()?;
@@ -142,21 +429,20 @@ impl YandexMusicClient {
 
         let params: HashMap<&str, &str> = HashMap::from([
             ("ts", ts.as_str()),
-            ("trackId", track_id),
+            ("trackId", track_id.as_str()),
             ("quality", quality),
             ("codecs", "flac,aac,he-aac,mp3"),
             ("transports", "raw"),
             ("sign", &signature),
         ]);
 
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             .header(AUTHORIZATION, &self.token)
             .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
-            .query(&params)
-            .send()?;
+            .query(&params))?;
 
-        resp.error_for_status_ref()?;
-        let meta: FileInfo = resp.json()?;
+        let meta: FileInfo = parse_response(resp)?;
+        self.cache_set(&key, &meta.result.download_info)?;
         Ok(meta.result.download_info)
     }
 
@@ -169,79 +455,281 @@ impl YandexMusicClient {
             ("count", "1000"),
         ]);
 
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             .header(AUTHORIZATION, &self.token)
             .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
-            .query(&params)
-            .send()?;
+            .query(&params))?;
 
-        resp.error_for_status_ref()?;
-        let meta: UserPlaylistsMeta = resp.json()?;
+        let meta: UserPlaylistsMeta = parse_response(resp)?;
         Ok(meta.result)
     }
 
-    pub fn get_other_user_playlist_meta(&mut self, username: &str, id: &str) -> Result<OtherUserPlaylistMetaResult, Box<dyn Error>> {
+    pub fn get_other_user_playlist_meta(&mut self, username: &UserLogin, id: &str) -> Result<OtherUserPlaylistMetaResult, Box<dyn Error>> {
         let url = format!("{}/users/{}/playlists/{}", BASE_URL, username, id);
 
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             .header(AUTHORIZATION, &self.token)
-            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
-            .send()?;
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT))?;
 
-        resp.error_for_status_ref()?;
-        let meta: OtherUserPlaylistMeta = resp.json()?;
+        let meta: OtherUserPlaylistMeta = parse_response(resp)?;
         Ok(meta.result)
     }
 
-    pub fn get_playlist_meta(&mut self, uuid: &str) -> Result<PlaylistMetaResult, Box<dyn Error>> {
+    pub fn get_playlist_meta(&mut self, uuid: &PlaylistId) -> Result<PlaylistMetaResult, Box<dyn Error>> {
         let url = format!("{}/playlist/{}", BASE_URL, uuid);
 
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             .header(AUTHORIZATION, &self.token)
-            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
-            .send()?;
-
-        resp.error_for_status_ref()?;
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT))?;
 
-        let meta: PlaylistMeta = resp.json()?;
+        let meta: PlaylistMeta = parse_response(resp)?;
         Ok(meta.result)
     }
 
-    pub fn get_artist_meta(&mut self, artist_id: &str) -> Result<ArtistMetaResult, Box<dyn Error>> {
+    pub fn get_artist_meta(&mut self, artist_id: &ArtistId) -> Result<ArtistMetaResult, Box<dyn Error>> {
+        let key = format!("artist:{}", artist_id);
+        if let Some(cached) = self.cache_get(&key, self.cache_ttl) {
+            return Ok(cached);
+        }
+
         let url = format!("{}/artists/{}", BASE_URL, artist_id);
 
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
+            .header(AUTHORIZATION, &self.token)
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT))?;
+
+        let meta: ArtistMeta = parse_response(resp)?;
+        self.cache_set(&key, &meta.result)?;
+        Ok(meta.result)
+    }
+
+    // Artist track ids sorted by popularity, distinct from the album listing get_artist_meta
+    // returns; resolved to full metadata afterwards via get_tracks_meta.
+    pub fn get_artist_track_ids(&mut self, artist_id: &ArtistId) -> Result<Vec<String>, Box<dyn Error>> {
+        let url = format!("{}/artists/{}/track-ids-by-rating", BASE_URL, artist_id);
+
+        let params: HashMap<&str, &str> = HashMap::from([
+            ("page", "0"),
+            ("pageSize", "100"),
+        ]);
+
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             .header(AUTHORIZATION, &self.token)
             .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
-            .send()?;
+            .query(&params))?;
 
-        resp.error_for_status_ref()?;
+        let meta: ArtistTracksMeta = parse_response(resp)?;
+        Ok(meta.result.track_ids.into_iter().map(|t| t.id).collect())
+    }
 
-        let meta: ArtistMeta = resp.json()?;
-        Ok(meta.result)
+    // Ids of a user's liked tracks, resolved to full metadata afterwards via get_tracks_meta;
+    // not to be confused with get_user_favourites_meta, which resolves the "Liked Tracks"
+    // playlist owned by the authenticated user.
+    pub fn get_liked_track_ids(&mut self, login: &UserLogin) -> Result<Vec<String>, Box<dyn Error>> {
+        let url = format!("{}/users/{}/likes/tracks", BASE_URL, login);
+
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
+            .header(AUTHORIZATION, &self.token)
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT))?;
+
+        let meta: LikedTracksMeta = parse_response(resp)?;
+        Ok(meta.result.library.tracks.into_iter().map(|t| t.id).collect())
     }
 
     pub fn get_user_favourites_meta(&mut self) -> Result<UserFavouritesMeta, Box<dyn Error>> {
         let url = format!("{}/landing/block/likes-and-history", BASE_URL);
 
-        let resp = self.c.get(url)
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
             .header(AUTHORIZATION, &self.token)
-            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
-            .send()?;
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT))?;
 
-        resp.error_for_status_ref()?;
-        let meta: UserFavouritesMeta = resp.json()?;
+        let meta: UserFavouritesMeta = parse_response(resp)?;
         Ok(meta)
     }
 
-    pub fn get_file_resp(&mut self, url: &str, with_range: bool) -> Result<ReqwestResp, ReqwestErr> {
-        let mut req = self.c.get(url);
-        if with_range {
-            req = req.header(RANGE, "bytes=0-")
-        }
-        let resp = req.send()?;
-        resp.error_for_status_ref()?;
+    pub fn search(&mut self, query: &str, search_type: &str, page: u32) -> Result<SearchResult, Box<dyn Error>> {
+        let url = format!("{}/search", BASE_URL);
+        let page_str = page.to_string();
+
+        let params: HashMap<&str, &str> = HashMap::from([
+            ("text", query),
+            ("type", search_type),
+            ("page", page_str.as_str()),
+        ]);
+
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
+            .header(AUTHORIZATION, &self.token)
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
+            .query(&params))?;
+
+        let meta: Search = parse_response(resp)?;
+        Ok(meta.result)
+    }
+
+    // Resolves bare track ids to full metadata in one request; the API accepts the ids as a
+    // single comma-separated `track-ids` form field.
+    pub fn get_tracks_meta(&mut self, ids: &[TrackId]) -> Result<Vec<TrackMeta>, Box<dyn Error>> {
+        let url = format!("{}/tracks", BASE_URL);
+        let track_ids = ids.iter().map(TrackId::as_str).collect::<Vec<&str>>().join(",");
+
+        let params: HashMap<&str, &str> = HashMap::from([
+            ("track-ids", track_ids.as_str()),
+        ]);
+
+        let resp = self.send_with_retry(|| self.c.post(url.as_str())
+            .header(AUTHORIZATION, &self.token)
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
+            .form(&params))?;
+
+        let meta: TracksMeta = parse_response(resp)?;
+        Ok(meta.result)
+    }
+
+    pub fn get_chart_meta(&mut self, country: &str) -> Result<ChartResult, Box<dyn Error>> {
+        let url = format!("{}/landing/block/chart", BASE_URL);
+
+        let params: HashMap<&str, &str> = HashMap::from([
+            ("blocks", "chart"),
+            ("country", country),
+        ]);
+
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
+            .header(AUTHORIZATION, &self.token)
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
+            .query(&params))?;
+
+        let meta: Chart = parse_response(resp)?;
+        Ok(meta.result)
+    }
+
+    pub fn get_new_releases_meta(&mut self, country: &str) -> Result<NewReleasesResult, Box<dyn Error>> {
+        let url = format!("{}/landing/block/new-releases", BASE_URL);
+
+        let params: HashMap<&str, &str> = HashMap::from([
+            ("country", country),
+        ]);
+
+        let resp = self.send_with_retry(|| self.c.get(url.as_str())
+            .header(AUTHORIZATION, &self.token)
+            .header("X-Yandex-Music-Client", YANDEX_USER_AGENT)
+            .query(&params))?;
+
+        let meta: NewReleases = parse_response(resp)?;
+        Ok(meta.result)
+    }
+
+    pub fn get_file_resp(&mut self, url: &str, with_range: bool) -> Result<ReqwestResp, Box<dyn Error>> {
+        let resp = self.send_with_retry(|| {
+            let mut req = self.c.get(url);
+            if with_range {
+                req = req.header(RANGE, "bytes=0-")
+            }
+            req
+        })?;
         Ok(resp)
     }
 
+    // Used to resume a partially-downloaded file from `resume_from` bytes in.
+    pub fn get_file_resp_from(&mut self, url: &str, resume_from: u64) -> Result<ReqwestResp, Box<dyn Error>> {
+        let resp = self.send_with_retry(|| self.c.get(url)
+            .header(RANGE, format!("bytes={}-", resume_from)))?;
+        Ok(resp)
+    }
+
+    // Fetches a single `start..=end` byte range of `url`. Callers check the response status
+    // themselves, since a 200 instead of 206 means the server ignored the Range header.
+    fn get_file_resp_range(&mut self, url: &str, start: u64, end: u64) -> Result<ReqwestResp, Box<dyn Error>> {
+        let resp = self.send_with_retry(|| self.c.get(url)
+            .header(RANGE, format!("bytes={}-{}", start, end)))?;
+        Ok(resp)
+    }
+
+    // Splits `url`'s content into fixed-size byte ranges and fetches them `concurrency` at a
+    // time, each over its own cloned client (mirrors `download_jobs`'s clone-per-thread
+    // pattern), writing into a `.rangetmp` sibling of `dest_path` at the matching offset and
+    // only renaming it onto `dest_path` once every range is confirmed written - so a failure
+    // partway through never leaves `dest_path` itself pre-allocated to its full size with
+    // unfetched ranges still zero-filled, which would otherwise fool `download_track`'s
+    // file-size-based resume check into treating the file as already complete. Returns
+    // Ok(false) instead of erroring the moment the first range request comes back without
+    // 206, so the caller can fall back to the existing single-stream `download_track` path.
+    pub fn download_file_parallel(&self, url: &str, dest_path: &PathBuf, concurrency: usize, chunk_size: u64) -> Result<bool, Box<dyn Error>> {
+        let mut probe = self.clone();
+        let probe_resp = probe.get_file_resp_range(url, 0, chunk_size - 1)?;
+        if probe_resp.status() != StatusCode::PARTIAL_CONTENT {
+            return Ok(false);
+        }
+
+        let content_length = probe_resp.headers().get(CONTENT_RANGE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit('/').next())
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or("range response is missing a Content-Range total size")?;
+        let first_bytes = probe_resp.bytes()?;
+
+        let mut ranges = Vec::new();
+        let mut start = chunk_size.min(content_length);
+        while start < content_length {
+            let end = (start + chunk_size - 1).min(content_length - 1);
+            ranges.push((start, end));
+            start = end + 1;
+        }
+
+        let tmp_path = utils::append_to_path_buf(dest_path, ".rangetmp");
+        let result = self.download_ranges_into(url, &tmp_path, content_length, &first_bytes, &ranges, concurrency);
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        result?;
+
+        fs::rename(&tmp_path, dest_path)?;
+        Ok(true)
+    }
+
+    fn download_ranges_into(&self, url: &str, tmp_path: &PathBuf, content_length: u64, first_bytes: &[u8], ranges: &[(u64, u64)], concurrency: usize) -> Result<(), Box<dyn Error>> {
+        let file = OpenOptions::new().create(true).write(true).truncate(true).open(tmp_path)?;
+        file.set_len(content_length)?;
+        let file = Mutex::new(file);
+        file.lock().unwrap().write_all(first_bytes)?;
+
+        let failed = Mutex::new(false);
+        for batch in ranges.chunks(concurrency.max(1)) {
+            thread::scope(|scope| {
+                for &(start, end) in batch {
+                    let mut worker = self.clone();
+                    let file = &file;
+                    let failed = &failed;
+                    scope.spawn(move || {
+                        let result: Result<(), Box<dyn Error>> = (|| {
+                            let resp = worker.get_file_resp_range(url, start, end)?;
+                            if resp.status() != StatusCode::PARTIAL_CONTENT {
+                                return Err("server ignored the Range header for a chunk".into());
+                            }
+                            let bytes = resp.bytes()?;
+                            let mut f = file.lock().unwrap();
+                            f.seek(SeekFrom::Start(start))?;
+                            f.write_all(&bytes)?;
+                            Ok(())
+                        })();
+
+                        if let Err(e) = result {
+                            println!("Chunk download failed.\n{:?}", e);
+                            *failed.lock().unwrap() = true;
+                        }
+                    });
+                }
+            });
+
+            if *failed.lock().unwrap() {
+                return Err("one or more chunk downloads failed".into());
+            }
+        }
+
+        let written = file.into_inner()?.metadata()?.len();
+        if written != content_length {
+            return Err("range download finished short of the expected content length".into());
+        }
+        Ok(())
+    }
+
 }
\ No newline at end of file
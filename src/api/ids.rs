@@ -0,0 +1,148 @@
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+
+// Resource-id newtypes, one per endpoint family, so a getter can't be called with e.g. an
+// album id where a track id is expected - passing the wrong one is now a type error instead
+// of an opaque 404. `new` borrows the given &str with no allocation, which matters on hot
+// paths like iterating a large playlist. URL parsing (pulling one of these ids out of a full
+// Yandex Music URL) is main.rs's job, via `check_url`/`REGEX_STRINGS` - these types only
+// validate and wrap an id that's already been extracted.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdParseError(String);
+
+impl fmt::Display for IdParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for IdParseError {}
+
+fn is_numeric(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_digit())
+}
+
+// Yandex Music playlist uuids are plain lowercase-hex UUIDs (8-4-4-4-12).
+fn is_uuid(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() == 36 && bytes.iter().enumerate().all(|(i, b)| match i {
+        8 | 13 | 18 | 23 => *b == b'-',
+        _ => b.is_ascii_hexdigit(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackId<'a>(Cow<'a, str>);
+
+impl<'a> TrackId<'a> {
+    pub fn new(id: &'a str) -> Result<Self, IdParseError> {
+        if !is_numeric(id) {
+            return Err(IdParseError(format!("track id must be numeric, got {:?}", id)));
+        }
+        Ok(Self(Cow::Borrowed(id)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TrackId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlbumId<'a>(Cow<'a, str>);
+
+impl<'a> AlbumId<'a> {
+    pub fn new(id: &'a str) -> Result<Self, IdParseError> {
+        if !is_numeric(id) {
+            return Err(IdParseError(format!("album id must be numeric, got {:?}", id)));
+        }
+        Ok(Self(Cow::Borrowed(id)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AlbumId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtistId<'a>(Cow<'a, str>);
+
+impl<'a> ArtistId<'a> {
+    pub fn new(id: &'a str) -> Result<Self, IdParseError> {
+        if !is_numeric(id) {
+            return Err(IdParseError(format!("artist id must be numeric, got {:?}", id)));
+        }
+        Ok(Self(Cow::Borrowed(id)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for ArtistId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// A playlist's real id, the uuid returned by `get_user_playlists_meta`/
+// `get_other_user_playlist_meta`, as opposed to the small per-user playlist `kind` found in
+// a playlist URL (that one stays a bare numeric string; it's an index, not a resource id).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlaylistId<'a>(Cow<'a, str>);
+
+impl<'a> PlaylistId<'a> {
+    pub fn new(id: &'a str) -> Result<Self, IdParseError> {
+        if !is_uuid(id) {
+            return Err(IdParseError(format!("playlist id must be a uuid, got {:?}", id)));
+        }
+        Ok(Self(Cow::Borrowed(id)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for PlaylistId<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserLogin<'a>(Cow<'a, str>);
+
+impl<'a> UserLogin<'a> {
+    pub fn new(login: &'a str) -> Result<Self, IdParseError> {
+        if login.is_empty() {
+            return Err(IdParseError("user login can't be empty".to_string()));
+        }
+        Ok(Self(Cow::Borrowed(login)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for UserLogin<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
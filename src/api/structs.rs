@@ -1,10 +1,20 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+// Implemented by any track-like metadata struct that carries packed allowed/forbidden
+// country-code restriction lists, so `YandexMusicClient::is_track_available` can work
+// across `Volume` and `PlaylistTrack` without duplicating the check per type.
+pub trait Restricted {
+    fn allowed_countries(&self) -> Option<&str>;
+    fn forbidden_countries(&self) -> Option<&str>;
+}
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UserInfoResult {
     pub has_plus: bool,
     pub login: String,
+    #[serde(default)]
+    pub region: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -12,11 +22,13 @@ pub struct UserInfo {
     pub result: UserInfoResult,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LyricsInfo {
     pub has_available_sync_lyrics: bool,
     pub has_available_text_lyrics: bool,
+    #[serde(default)]
+    pub instrumental: bool,
 }
 
 impl LyricsInfo {
@@ -30,7 +42,7 @@ impl LyricsInfo {
         }
     }
 }
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Volume {
     pub artists: Vec<Artist>,
@@ -39,19 +51,41 @@ pub struct Volume {
     pub available: bool,
     pub lyrics_info: Option<LyricsInfo>,
     pub version: Option<String>,
+    #[serde(default)]
+    pub explicit: bool,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    // Packed two-letter ISO country codes (e.g. "RUKZBY") this track is allowed/forbidden
+    // in; see `Restricted`/`YandexMusicClient::is_track_available`.
+    #[serde(default)]
+    pub allowed: Option<String>,
+    #[serde(default)]
+    pub forbidden: Option<String>,
 }
 
-#[derive(Deserialize)]
+impl Restricted for Volume {
+    fn allowed_countries(&self) -> Option<&str> {
+        self.allowed.as_deref()
+    }
+
+    fn forbidden_countries(&self) -> Option<&str> {
+        self.forbidden.as_deref()
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct Artist {
     pub name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct Label {
     pub name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AlbumResult {
     pub title: String,
@@ -63,6 +97,8 @@ pub struct AlbumResult {
     pub version: Option<String>,
     pub volumes: Vec<Vec<Volume>>,
     pub year: Option<u16>,
+    #[serde(default)]
+    pub release_date: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -81,7 +117,7 @@ pub struct LyricsMeta {
     pub result: LyricsResult,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct DownloadInfo {
     // pub quality: String,
     pub key: String,
@@ -157,6 +193,66 @@ pub struct PlaylistTrack {
     pub cover_uri: Option<String>,
     pub version: Option<String>,
     pub track_source: String,
+    #[serde(default)]
+    pub explicit: bool,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub allowed: Option<String>,
+    #[serde(default)]
+    pub forbidden: Option<String>,
+}
+
+impl Restricted for PlaylistTrack {
+    fn allowed_countries(&self) -> Option<&str> {
+        self.allowed.as_deref()
+    }
+
+    fn forbidden_countries(&self) -> Option<&str> {
+        self.forbidden.as_deref()
+    }
+}
+
+// A single entry from the batch `/tracks` endpoint, used to resolve a bare track id to full
+// metadata without going through an album or playlist.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrackMeta {
+    pub id: String,
+    pub title: String,
+    pub available: bool,
+    pub lyrics_info: Option<LyricsInfo>,
+    pub albums: Vec<AlbumResultInPlaylist>,
+    pub artists: Vec<Artist>,
+    pub cover_uri: Option<String>,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub explicit: bool,
+    #[serde(default)]
+    pub composer: Option<String>,
+    #[serde(default)]
+    pub isrc: Option<String>,
+    #[serde(default)]
+    pub allowed: Option<String>,
+    #[serde(default)]
+    pub forbidden: Option<String>,
+}
+
+impl Restricted for TrackMeta {
+    fn allowed_countries(&self) -> Option<&str> {
+        self.allowed.as_deref()
+    }
+
+    fn forbidden_countries(&self) -> Option<&str> {
+        self.forbidden.as_deref()
+    }
+}
+
+#[derive(Deserialize)]
+pub struct TracksMeta {
+    pub result: Vec<TrackMeta>,
 }
 
 #[derive(Deserialize)]
@@ -169,6 +265,8 @@ pub struct AlbumResultInPlaylist {
     pub labels: Vec<Label>,
     pub version: Option<String>,
     pub year: Option<u16>,
+    #[serde(default)]
+    pub release_date: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -190,17 +288,17 @@ pub struct PlaylistMeta {
     pub result: PlaylistMetaResult,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct ArtistMetaAlbum {
     pub id: u64,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct ArtistMetaArtist {
     pub name: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct ArtistMetaResult {
     pub albums: Vec<ArtistMetaAlbum>,
     pub artist: ArtistMetaArtist,
@@ -211,6 +309,43 @@ pub struct ArtistMeta {
     pub result: ArtistMetaResult,
 }
 
+#[derive(Deserialize)]
+pub struct ArtistTrackId {
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtistTracksResult {
+    pub track_ids: Vec<ArtistTrackId>,
+}
+
+#[derive(Deserialize)]
+pub struct ArtistTracksMeta {
+    pub result: ArtistTracksResult,
+}
+
+#[derive(Deserialize)]
+pub struct LikedTrackId {
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+pub struct LikedTracksLibrary {
+    pub tracks: Vec<LikedTrackId>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LikedTracksResult {
+    pub library: LikedTracksLibrary,
+}
+
+#[derive(Deserialize)]
+pub struct LikedTracksMeta {
+    pub result: LikedTracksResult,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Favourites {
@@ -231,4 +366,84 @@ pub struct OtherUserPlaylistMetaResult {
 #[derive(Deserialize)]
 pub struct OtherUserPlaylistMeta {
     pub result: OtherUserPlaylistMetaResult,
+}
+
+#[derive(Deserialize)]
+pub struct SearchTrackAlbumRef {
+    pub id: u64,
+}
+
+#[derive(Deserialize)]
+pub struct SearchTrack {
+    pub id: String,
+    pub title: String,
+    pub artists: Vec<Artist>,
+    #[serde(default = "Vec::new")]
+    pub albums: Vec<SearchTrackAlbumRef>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchAlbum {
+    pub id: u64,
+    pub title: String,
+    pub artists: Vec<Artist>,
+    pub year: Option<u16>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchArtist {
+    pub id: u64,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct SearchPlaylist {
+    pub uid: u64,
+    pub kind: u32,
+    pub title: String,
+    pub owner: Owner,
+}
+
+#[derive(Deserialize)]
+pub struct SearchResultsPage<T> {
+    #[serde(default = "Vec::new")]
+    pub results: Vec<T>,
+}
+
+#[derive(Deserialize)]
+pub struct SearchResult {
+    #[serde(default)]
+    pub tracks: Option<SearchResultsPage<SearchTrack>>,
+    #[serde(default)]
+    pub albums: Option<SearchResultsPage<SearchAlbum>>,
+    #[serde(default)]
+    pub artists: Option<SearchResultsPage<SearchArtist>>,
+    #[serde(default)]
+    pub playlists: Option<SearchResultsPage<SearchPlaylist>>,
+}
+
+#[derive(Deserialize)]
+pub struct Search {
+    pub result: SearchResult,
+}
+
+#[derive(Deserialize)]
+pub struct ChartResult {
+    pub tracks: Vec<PlaylistTrackItem>,
+}
+
+#[derive(Deserialize)]
+pub struct Chart {
+    pub result: ChartResult,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewReleasesResult {
+    pub new_releases: Vec<u64>,
+}
+
+#[derive(Deserialize)]
+pub struct NewReleases {
+    pub result: NewReleasesResult,
 }
\ No newline at end of file
@@ -0,0 +1,64 @@
+use regex::Regex;
+
+use crate::structs::ParsedAlbumMeta;
+
+const TIMESTAMP_REGEX_STRING: &str = r#"\[(\d+):(\d+(?:\.\d+)?)\]"#;
+
+// A single synced line: offset from the start of the track, in milliseconds, plus its text.
+pub struct LrcLine {
+    pub offset_ms: u32,
+    pub text: String,
+}
+
+fn format_timestamp(offset_ms: u32) -> String {
+    let minutes = offset_ms / 60_000;
+    let seconds = (offset_ms % 60_000) as f64 / 1000.0;
+    format!("{:02}:{:05.2}", minutes, seconds)
+}
+
+// Parses raw LRC text (as returned by the lyrics endpoint) into timestamp/line pairs,
+// dropping any metadata ([ti:]/[ar:]/etc.) lines that don't carry a timestamp.
+pub fn parse(text: &str) -> Result<Vec<LrcLine>, regex::Error> {
+    let re = Regex::new(TIMESTAMP_REGEX_STRING)?;
+    let mut lines = Vec::new();
+
+    for line in text.lines() {
+        let capture = match re.captures(line) {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let minutes: u32 = capture[1].parse().unwrap_or(0);
+        let seconds: f64 = capture[2].parse().unwrap_or(0.0);
+        let offset_ms = minutes * 60_000 + (seconds * 1000.0) as u32;
+        let text = re.replace(line, "").trim().to_string();
+
+        lines.push(LrcLine { offset_ms, text });
+    }
+
+    Ok(lines)
+}
+
+// Renders an LRC file: [ti:]/[ar:]/[al:] header tags from `meta`, followed by one
+// `[mm:ss.xx]line` entry per synced line.
+pub fn build(meta: &ParsedAlbumMeta, lines: &[LrcLine]) -> String {
+    let mut out = String::new();
+
+    out.push_str(&format!("[ti:{}]\n", meta.title));
+    out.push_str(&format!("[ar:{}]\n", meta.artist));
+    out.push_str(&format!("[al:{}]\n", meta.album_title));
+
+    for line in lines {
+        out.push_str(&format!("[{}]{}\n", format_timestamp(line.offset_ms), line.text));
+    }
+
+    out
+}
+
+// Strips timestamps, joining the bare lyric lines with newlines, for plain-text/unsynced use.
+pub fn strip_timestamps(lines: &[LrcLine]) -> String {
+    lines.iter()
+        .map(|l| l.text.as_str())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
@@ -6,47 +6,109 @@ use std::path::PathBuf;
 use regex::{Regex, Error as RegexError};
 use std::{thread, time};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::process::{Command, Stdio};
 
 use aes::Aes128;
 use clap::Parser;
-use reqwest::Error as ReqwestErr;
 use ctr::cipher::{KeyIvInit, StreamCipher};
-use indicatif::{ProgressBar, ProgressStyle};
-use metaflac::{Tag as FlacTag, Error as FlacError};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use metaflac::Tag as FlacTag;
 use metaflac::block::PictureType::CoverFront as FLACCoverFront;
-use id3::{Error as ID3Error, Tag as Mp3Tag, TagLike, Version};
-use id3::frame::{Picture as Mp3Image};
+use id3::{Tag as Mp3Tag, TagLike, Version};
+use id3::frame::{Picture as Mp3Image, Lyrics as Mp3Lyrics, SynchronisedLyrics, SynchronisedLyricsType, TimestampFormat};
 use id3::frame::PictureType::CoverFront as MP3CoverFront;
-use mp4ameta::{Tag as Mp4Tag, Data as Mp4Data, Fourcc, Error as MP4Error};
+use mp4ameta::{Tag as Mp4Tag, Data as Mp4Data, Fourcc, Ident};
 
-use crate::api::client::YandexMusicClient;
+use crate::api::client::{ClientError, TokenReport, YandexMusicClient};
+use crate::api::ids::{AlbumId, ArtistId, PlaylistId, TrackId, UserLogin};
 use crate::api::structs::*;
-use crate::structs::{Args, Config, ParsedAlbumMeta};
+use crate::structs::{Args, Config, Country, ParsedAlbumMeta, QualityPreset};
 
 mod api;
+mod lrc;
+mod manifest;
+mod mp4demux;
+mod musicbrainz;
 mod structs;
 mod utils;
 
 const BUF_SIZE: usize = 1024 * 1024;
 
+// Segment size used by the parallel chunked download path (matches librespot's fetch
+// chunk size), and how many segments to fetch at once.
+const DOWNLOAD_CHUNK_SIZE: u64 = 0x20000;
+const DOWNLOAD_CHUNK_CONCURRENCY: usize = 4;
+
 #[cfg(target_os = "windows")]
 const IS_WINDOWS: bool = true;
 
 #[cfg(not(target_os = "windows"))]
 const IS_WINDOWS: bool = false;
 
-const REGEX_STRINGS: [&str; 3] = [
-    r#"^https://music\.yandex\.(?:by|kz|ru)/album/(\d+)(?:/track/(\d+)(?:\?.+)?)?$"#,
-    r#"^https://music\.yandex\.(?:by|kz|ru)/users/(.+)/playlists/(\d+)(?:\?.+)?$"#,
-    r#"^https://music\.yandex\.(?:by|kz|ru)/artist/(\d+)(?:/albums)?(?:\?.+)?$"#,
+// Yandex Music serves the same catalog across all of these regional TLDs; any link copied
+// from one should parse the same as the others.
+const REGEX_STRINGS: [&str; 5] = [
+    r#"^https://music\.yandex\.(?:by|kz|ru|ua|com)/album/(\d+)(?:/track/(\d+)(?:\?.+)?)?$"#,
+    r#"^https://music\.yandex\.(?:by|kz|ru|ua|com)/users/(.+)/playlists/(\d+)(?:\?.+)?$"#,
+    r#"^https://music\.yandex\.(?:by|kz|ru|ua|com)/artist/(\d+)(?:/albums)?(?:\?.+)?$"#,
+    r#"^https://music\.yandex\.(?:by|kz|ru|ua|com)/artist/(\d+)/tracks(?:\?.+)?$"#,
+    r#"^https://music\.yandex\.(?:by|kz|ru|ua|com)/users/(.+)/likes/tracks(?:\?.+)?$"#,
 ];
 
 type Aes128Ctr = ctr::Ctr128BE<Aes128>; // AES-128 in CTR mode
 
-fn read_config() -> Result<Config, Box<dyn Error>> {
+const CONFIG_TEMPLATE: &str = r#"album_template = "{album_artist} - {album_title}"
+track_template = "{track_num_pad}. {title}"
+format = 4
+keep_covers = false
+out_path = ""
+get_original_covers = false
+token = ""
+sleep = false
+use_ffmpeg_env_var = false
+write_covers = false
+write_lyrics = false
+"#;
+
+// Resolves config.toml with precedence: explicit `--config PATH` > current working
+// directory > platform config dir > exe directory (the legacy, pre-chunk1-9 location).
+// If none of those has one, writes a blank template into the platform config dir so the
+// user has something to edit on first run.
+fn resolve_config_path(explicit: &Option<PathBuf>) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(path) = explicit {
+        return Ok(path.clone());
+    }
+
+    let cwd_path = PathBuf::from("config.toml");
+    if cwd_path.exists() {
+        return Ok(cwd_path);
+    }
+
+    if let Ok(dir) = utils::get_config_dir() {
+        let path = dir.join("config.toml");
+        if path.exists() {
+            return Ok(path);
+        }
+    }
+
     let exe_path = utils::get_exe_path()?;
-    let config_path = exe_path.join("config.toml");
+    let exe_config_path = exe_path.join("config.toml");
+    if exe_config_path.exists() {
+        return Ok(exe_config_path);
+    }
+
+    let dir = utils::get_config_dir()?;
+    fs::create_dir_all(&dir)?;
+    let template_path = dir.join("config.toml");
+    fs::write(&template_path, CONFIG_TEMPLATE)?;
+    println!("No config.toml found; wrote a blank template to {}", template_path.display());
+    Ok(template_path)
+}
+
+fn read_config(explicit: &Option<PathBuf>) -> Result<Config, Box<dyn Error>> {
+    let config_path = resolve_config_path(explicit)?;
+    println!("Loaded config from {}", config_path.display());
     let data = fs::read_to_string(config_path)?;
     let config: Config = toml::from_str(&data)?;
     Ok(config)
@@ -63,8 +125,9 @@ fn resolve_format(fmt: u8) -> Option<String> {
 }
 
 fn parse_config() -> Result<Config, Box<dyn Error>> {
-    let mut config = read_config()?;
-    if config.token.trim().is_empty() {
+    let args = Args::parse();
+    let mut config = read_config(&args.config)?;
+    if config.token.trim().is_empty() && args.token_file.is_none() {
         return Err("token can't be empty".into())
     }
 
@@ -76,8 +139,8 @@ fn parse_config() -> Result<Config, Box<dyn Error>> {
         config.track_template = "{track_num_pad}. {title}".to_string();
     }
 
-    let args = Args::parse();
-    let proc_urls = utils::process_urls(&args.urls)?;
+    let skip_url_parsing = args.search.is_some() || args.charts.is_some() || args.new;
+    let proc_urls = if skip_url_parsing { Vec::new() } else { utils::process_urls(&args.urls)? };
 
     if args.keep_covers {
         config.keep_covers = args.keep_covers;
@@ -87,6 +150,12 @@ fn parse_config() -> Result<Config, Box<dyn Error>> {
         config.sleep = args.sleep;
     }
 
+    config.sleep_min = args.sleep_min.unwrap_or(config.sleep_min);
+    config.sleep_max = args.sleep_max.unwrap_or(config.sleep_max);
+    if config.sleep_min > config.sleep_max {
+        return Err("sleep_min can't be greater than sleep_max".into());
+    }
+
     if args.write_covers {
         config.write_covers = args.write_covers;
     }
@@ -95,6 +164,10 @@ fn parse_config() -> Result<Config, Box<dyn Error>> {
         config.write_lyrics = args.write_lyrics;
     }
 
+    if args.embed_lyrics {
+        config.embed_lyrics = args.embed_lyrics;
+    }
+
     if args.get_original_covers {
         config.get_original_covers = args.get_original_covers;
     }
@@ -111,7 +184,30 @@ fn parse_config() -> Result<Config, Box<dyn Error>> {
     config.format_str = resolve_format(config.format)
         .ok_or("format must be between 1 and 4")?;
 
+    config.quality = args.quality.or(config.quality);
+    config.quality_chain = match config.quality {
+        Some(preset) => preset.format_chain().iter().map(|s| s.to_string()).collect(),
+        None => vec![config.format_str.clone()],
+    };
+
     config.urls = proc_urls;
+    config.search_query = args.search;
+    config.charts_country = args.charts;
+    config.new_releases = args.new;
+    config.force = args.force;
+    config.skip_genre = args.skip_genre;
+    config.only_artist = args.only_artist;
+    config.skip_explicit = args.skip_explicit;
+    config.musicbrainz = args.musicbrainz;
+    config.concurrency = args.concurrency.unwrap_or(config.concurrency).max(1);
+    config.download_sem = utils::Semaphore::new(config.concurrency);
+    config.lyrics_only = args.lyrics_only;
+    config.max_retries = args.max_retries.unwrap_or(config.max_retries);
+    config.cache = args.cache;
+    config.cache_ttl = args.cache_ttl.unwrap_or(config.cache_ttl);
+    config.clear_cache = args.clear_cache;
+    config.token_file = args.token_file;
+    config.manifest = std::sync::Mutex::new(manifest::Manifest::load(&config.out_path)?);
     Ok(config)
 }
 
@@ -146,6 +242,54 @@ fn parse_labels(labels: &[Label]) -> String {
         .join(", ")
 }
 
+// Naive "Last, First" sort-name derivation: reorders a two-word "First Last" name,
+// leaving anything else (multiple comma-joined artists, single words, longer names)
+// untouched. Two-word band names are reordered the same way as personal names.
+fn sort_name(name: &str) -> String {
+    let words: Vec<&str> = name.split_whitespace().collect();
+    match words.as_slice() {
+        [first, last] if !name.contains(',') => format!("{}, {}", last, first),
+        _ => name.to_string(),
+    }
+}
+
+fn parse_copyright(year: Option<u16>, label: &str) -> Option<String> {
+    if label.is_empty() {
+        return None;
+    }
+    match year {
+        Some(year) => Some(format!("\u{00a9} {} {}", year, label)),
+        None => Some(format!("\u{00a9} {}", label)),
+    }
+}
+
+// Returns false (and prints why) if meta should be skipped per the configured filters.
+fn passes_filters(config: &Config, meta: &ParsedAlbumMeta) -> bool {
+    if config.skip_explicit && meta.explicit {
+        println!("Skipped explicit track.");
+        return false;
+    }
+
+    if let Some(genre) = &meta.genre {
+        if config.skip_genre.iter().any(|g| g.eq_ignore_ascii_case(genre)) {
+            println!("Skipped track with genre: {}", genre);
+            return false;
+        }
+    }
+
+    if !config.only_artist.is_empty() {
+        let lower_artist = meta.artist.to_lowercase();
+        let matches = config.only_artist.iter()
+            .any(|a| lower_artist.contains(&a.to_lowercase()));
+        if !matches {
+            println!("Skipped track not by an allowed artist.");
+            return false;
+        }
+    }
+
+    true
+}
+
 fn parse_title(title: &str, version: Option<String>) -> String {
     format!(
         "{}{}",
@@ -158,19 +302,34 @@ fn parse_title(title: &str, version: Option<String>) -> String {
 // Clean these four up.
 fn parse_album_meta(meta: &AlbumResult, track_total: u16) -> ParsedAlbumMeta {
     let album_title = parse_title(&meta.title, meta.version.clone());
+    let album_artist = parse_artists(&meta.artists);
+    let album_artist_sort = sort_name(&album_artist);
+    let label = parse_labels(&meta.labels);
+    let copyright = parse_copyright(meta.year, &label);
 
     ParsedAlbumMeta {
-        album_artist: parse_artists(&meta.artists),
+        album_artist,
+        album_artist_sort,
         album_title,
         artist: String::new(),
+        artist_sort: String::new(),
+        composer: None,
+        copyright,
         cover_data: Vec::new(),
+        disc_num: 1,
+        disc_total: meta.volumes.len() as u16,
+        disc_track_total: track_total,
+        explicit: false,
         genre: meta.genre.clone(),
+        instrumental: false,
+        isrc: None,
         lyrics_avail: None,
         is_track_only: false,
         title: String::new(),
         track_num: 0,
         track_total,
-        label: parse_labels(&meta.labels),
+        label,
+        release_date: meta.release_date.clone(),
         timed_lyrics: None,
         untimed_lyrics: None,
         year: meta.year,
@@ -178,22 +337,67 @@ fn parse_album_meta(meta: &AlbumResult, track_total: u16) -> ParsedAlbumMeta {
 }
 
 fn get_lyrics_text(c: &mut YandexMusicClient, track_id: &str, timed: bool) -> Result<String, Box<dyn Error>> {
-    let lyrics_meta = c.get_lyrics_meta(track_id, timed)?;
+    let lyrics_meta = c.get_lyrics_meta(&TrackId::new(track_id)?, timed)?;
     let resp = c.get_file_resp(&lyrics_meta.download_url, false)?;
     let lyrics = resp.text()?;
 
     Ok(lyrics)
 }
 
+fn fetch_and_write_lyrics(c: &mut YandexMusicClient, track_id: &str, meta: &mut ParsedAlbumMeta, config: &Config, track_path_no_ext: &PathBuf) -> Result<(), Box<dyn Error>> {
+    if meta.instrumental {
+        println!("Instrumental - skipping lyrics.");
+        return Ok(());
+    }
+
+    let lyrics = match meta.lyrics_avail {
+        Some(lyrics) => lyrics,
+        None => return Ok(()),
+    };
+
+    let lyrics_text = get_lyrics_text(c, track_id, lyrics)?;
+    if lyrics {
+        if config.write_lyrics {
+            let lyrics_path = utils::append_to_path_buf(track_path_no_ext, ".lrc");
+            println!("Writing timed lyrics file...");
+            write_timed_lyrics(&lyrics_text, meta, &lyrics_path)?;
+        }
+        meta.timed_lyrics = Some(lyrics_text);
+    } else {
+        if config.write_lyrics {
+            let lyrics_path = utils::append_to_path_buf(track_path_no_ext, ".txt");
+            println!("Writing untimed lyrics file...");
+            fs::write(&lyrics_path, &lyrics_text)?;
+        }
+        meta.untimed_lyrics = Some(lyrics_text);
+    }
+
+    Ok(())
+}
+
 fn parse_album_meta_playlist(meta: &AlbumResultInPlaylist, track_total: u16) -> ParsedAlbumMeta {
     let album_title = parse_title(&meta.title, meta.version.clone());
+    let album_artist = parse_artists(&meta.artists);
+    let album_artist_sort = sort_name(&album_artist);
+    let label = parse_labels(&meta.labels);
+    let copyright = parse_copyright(meta.year, &label);
 
     ParsedAlbumMeta {
-        album_artist: parse_artists(&meta.artists),
+        album_artist,
+        album_artist_sort,
         album_title,
         artist: String::new(),
+        artist_sort: String::new(),
+        composer: None,
+        copyright,
         cover_data: Vec::new(),
+        disc_num: 1,
+        disc_total: 1,
+        disc_track_total: track_total,
+        explicit: false,
         genre: meta.genre.clone(),
+        instrumental: false,
+        isrc: None,
         lyrics_avail: None,
         is_track_only: false,
         title: String::new(),
@@ -201,7 +405,8 @@ fn parse_album_meta_playlist(meta: &AlbumResultInPlaylist, track_total: u16) ->
         track_total,
         timed_lyrics: None,
         untimed_lyrics: None,
-        label: parse_labels(&meta.labels),
+        label,
+        release_date: meta.release_date.clone(),
         year: meta.year,
     }
 }
@@ -210,10 +415,15 @@ fn parse_track_meta(meta: &mut ParsedAlbumMeta, track_meta: &Volume, track_num:
     let title = parse_title(&track_meta.title, track_meta.version.clone());
 
     meta.artist =  parse_artists(&track_meta.artists);
+    meta.artist_sort = sort_name(&meta.artist);
     meta.title = title;
     meta.track_num = track_num;
+    meta.explicit = track_meta.explicit;
+    meta.composer = track_meta.composer.clone();
+    meta.isrc = track_meta.isrc.clone();
     if let Some(lyrics) = &track_meta.lyrics_info {
         meta.lyrics_avail = lyrics.check_availibility();
+        meta.instrumental = lyrics.instrumental;
     }
     meta.is_track_only = is_track_only;
 }
@@ -222,15 +432,39 @@ fn parse_track_meta_playlist(meta: &mut ParsedAlbumMeta, track_meta: &PlaylistTr
     let title = parse_title(&track_meta.title, track_meta.version.clone());
 
     meta.artist =  parse_artists(&track_meta.artists);
+    meta.artist_sort = sort_name(&meta.artist);
     meta.title = title;
     meta.track_num = track_num;
+    meta.explicit = track_meta.explicit;
+    meta.composer = track_meta.composer.clone();
+    meta.isrc = track_meta.isrc.clone();
     if let Some(lyrics) = &track_meta.lyrics_info {
         meta.lyrics_avail = lyrics.check_availibility();
+        meta.instrumental = lyrics.instrumental;
     }
 
 }
 
-fn get_cover_data(c: &mut YandexMusicClient, url: &str, original: bool) -> Result<Vec<u8>, Box<ReqwestErr>> {
+// Same field mapping as parse_track_meta_playlist, but for the standalone TrackMeta shape
+// returned by get_tracks_meta; used by process_artist_tracks/process_user_liked_tracks,
+// which resolve bare track ids instead of walking an album's or playlist's own listing.
+fn parse_track_meta_trackmeta(meta: &mut ParsedAlbumMeta, track_meta: &TrackMeta, track_num: u16) {
+    let title = parse_title(&track_meta.title, track_meta.version.clone());
+
+    meta.artist = parse_artists(&track_meta.artists);
+    meta.artist_sort = sort_name(&meta.artist);
+    meta.title = title;
+    meta.track_num = track_num;
+    meta.explicit = track_meta.explicit;
+    meta.composer = track_meta.composer.clone();
+    meta.isrc = track_meta.isrc.clone();
+    if let Some(lyrics) = &track_meta.lyrics_info {
+        meta.lyrics_avail = lyrics.check_availibility();
+        meta.instrumental = lyrics.instrumental;
+    }
+}
+
+fn get_cover_data(c: &mut YandexMusicClient, url: &str, original: bool) -> Result<Vec<u8>, Box<dyn Error>> {
     let to_replace = if original { "/orig" } else { "/1000x1000" };
     let replaced_url = url.replace("/%%", to_replace);
     let full_url = format!("https://{}", replaced_url);
@@ -266,21 +500,49 @@ fn parse_specs(codec: &str, bitrate: u16) -> Option<(String, String)> {
     }
 }
 
-fn download_track(c: &mut YandexMusicClient, url: &str, out_path: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let mut resp = c.get_file_resp(url, true)?;
+// Resumes from an existing partial `out_path` (the caller's ".incomplete" file) if present,
+// so an interrupted transfer picks back up instead of restarting from zero.
+fn download_track(c: &mut YandexMusicClient, url: &str, out_path: &PathBuf, multi: &MultiProgress) -> Result<(), Box<dyn Error>> {
+    let already_downloaded = fs::metadata(out_path).map(|m| m.len()).unwrap_or(0);
+
+    // Only worth attempting on a fresh download; a resumed partial file falls through to
+    // the single-stream path below, which already knows how to pick up from `already_downloaded`.
+    if already_downloaded == 0 {
+        if c.download_file_parallel(url, out_path, DOWNLOAD_CHUNK_CONCURRENCY, DOWNLOAD_CHUNK_SIZE)? {
+            let total_size = fs::metadata(out_path)?.len();
+            let pb = multi.add(ProgressBar::new(total_size));
+            pb.set_style(ProgressStyle::with_template("[{elapsed_precise}] [{bar:40.cyan/blue}] {percent}% at {binary_bytes_per_sec}, {bytes}/{total_bytes} (ETA: {eta})")?
+                .progress_chars("#>-"));
+            pb.set_position(total_size);
+            pb.finish();
+            return Ok(());
+        }
+        // Server didn't honor range requests; fall through to the single-stream path below.
+    }
+
+    let mut resp = if already_downloaded > 0 {
+        c.get_file_resp_from(url, already_downloaded)?
+    } else {
+        c.get_file_resp(url, true)?
+    };
 
-    let total_size = resp
+    let remaining = resp
         .content_length()
         .ok_or("no content length header")?;
+    let total_size = already_downloaded + remaining;
 
-    let f = File::create(out_path)?;
+    let f = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(out_path)?;
     let mut writer = BufWriter::new(f);
     let mut buf = vec![0u8; BUF_SIZE];
 
-    let mut downloaded: usize = 0;
-    let pb = ProgressBar::new(total_size);
+    let mut downloaded: u64 = already_downloaded;
+    let pb = multi.add(ProgressBar::new(total_size));
     pb.set_style(ProgressStyle::with_template("[{elapsed_precise}] [{bar:40.cyan/blue}] {percent}% at {binary_bytes_per_sec}, {bytes}/{total_bytes} (ETA: {eta})")?
         .progress_chars("#>-"));
+    pb.set_position(downloaded);
 
     loop {
         let n = resp.read(&mut buf)?;
@@ -288,11 +550,16 @@ fn download_track(c: &mut YandexMusicClient, url: &str, out_path: &PathBuf) -> R
             break;
         }
         writer.write_all(&buf[..n])?;
-        downloaded += n;
-        pb.set_position(downloaded as u64);
+        downloaded += n as u64;
+        pb.set_position(downloaded);
     }
 
     pb.finish();
+
+    if downloaded != total_size {
+        return Err(format!("downloaded {} bytes but expected {}", downloaded, total_size).into());
+    }
+
     Ok(())
 }
 
@@ -308,149 +575,323 @@ fn set_vorbis_num(tag: &mut metaflac::Tag, key: &str, n: u16) {
     }
 }
 
-fn write_flac_tags(track_path: &PathBuf, meta: &ParsedAlbumMeta) -> Result<(), FlacError> {
-    let mut tag = FlacTag::read_from_path(&track_path)?;
+// A single write path per container format, selected by codec in write_tags(). Keeping
+// the field-by-field mapping behind one trait method per format means new fields or new
+// containers only need touching in one place instead of three parallel functions.
+trait Tagger {
+    fn write(&self, track_path: &PathBuf, meta: &ParsedAlbumMeta, config: &Config) -> Result<(), Box<dyn Error>>;
+}
 
-    set_vorbis(&mut tag, "ALBUM", &meta.album_title);
-    set_vorbis(&mut tag, "ALBUMARTIST", &meta.album_artist);
-    set_vorbis(&mut tag, "ARTIST", &meta.artist);
+struct FlacTagger;
+struct Mp3Tagger;
+struct Mp4Tagger;
 
-    set_vorbis(&mut tag, "LABEL", &meta.label);
-    set_vorbis(&mut tag, "TITLE", &meta.title);
+impl Tagger for FlacTagger {
+    fn write(&self, track_path: &PathBuf, meta: &ParsedAlbumMeta, config: &Config) -> Result<(), Box<dyn Error>> {
+        let mut tag = FlacTag::read_from_path(&track_path)?;
 
-    set_vorbis_num(&mut tag, "TRACKNUMBER", meta.track_num);
-    set_vorbis_num(&mut tag, "TRACKTOTAL", meta.track_total);
+        set_vorbis(&mut tag, "ALBUM", &meta.album_title);
+        set_vorbis(&mut tag, "ALBUMARTIST", &meta.album_artist);
+        set_vorbis(&mut tag, "ARTIST", &meta.artist);
 
-    if !meta.cover_data.is_empty() {
-        tag.add_picture("image/jpeg", FLACCoverFront, meta.cover_data.clone());
-    }
+        set_vorbis(&mut tag, "LABEL", &meta.label);
+        set_vorbis(&mut tag, "TITLE", &meta.title);
+        set_vorbis(&mut tag, "ARTISTSORT", &meta.artist_sort);
+        set_vorbis(&mut tag, "ALBUMARTISTSORT", &meta.album_artist_sort);
 
-    if let Some(genre) = &meta.genre {
-        set_vorbis(&mut tag, "GENRE", genre);
-    }
+        set_vorbis_num(&mut tag, "TRACKNUMBER", meta.track_num);
+        set_vorbis_num(&mut tag, "TRACKTOTAL", meta.track_total);
+        set_vorbis_num(&mut tag, "DISCNUMBER", meta.disc_num);
+        set_vorbis_num(&mut tag, "DISCTOTAL", meta.disc_total);
 
-    if let Some(year) = meta.year {
-        set_vorbis_num(&mut tag, "YEAR", year);
-    }
+        if let Some(composer) = &meta.composer {
+            set_vorbis(&mut tag, "COMPOSER", composer);
+        }
 
-    if let Some(lyrics) = &meta.untimed_lyrics {
-        set_vorbis(&mut tag, "UNSYNCEDLYRICS", lyrics);
-    }
+        if let Some(isrc) = &meta.isrc {
+            set_vorbis(&mut tag, "ISRC", isrc);
+        }
 
-    if let Some(lyrics) = &meta.timed_lyrics {
-        set_vorbis(&mut tag, "LYRICS", lyrics);
-    }
+        if let Some(copyright) = &meta.copyright {
+            set_vorbis(&mut tag, "COPYRIGHT", copyright);
+        }
 
-    tag.save()?;
-    Ok(())
-}
+        if let Some(release_date) = &meta.release_date {
+            set_vorbis(&mut tag, "DATE", release_date);
+        }
 
-fn write_mp3_tags(track_path: &PathBuf, meta: &ParsedAlbumMeta) -> Result<(), ID3Error> {
-    let mut tag = Mp3Tag::new();
+        if !meta.cover_data.is_empty() {
+            tag.add_picture("image/jpeg", FLACCoverFront, meta.cover_data.clone());
+        }
 
-    tag.set_album(&meta.album_title);
-    tag.set_album_artist(&meta.album_artist);
-    tag.set_artist(&meta.artist);
+        if let Some(genre) = &meta.genre {
+            set_vorbis(&mut tag, "GENRE", genre);
+        }
 
-    tag.set_title(&meta.title);
-    tag.set_track(meta.track_num as u32);
-    tag.set_total_tracks(meta.track_total as u32);
+        if let Some(year) = meta.year {
+            set_vorbis_num(&mut tag, "YEAR", year);
+        }
 
-    if !meta.cover_data.is_empty() {
-        let pic = Mp3Image {
-            mime_type: "image/jpeg".to_string(),
-            picture_type: MP3CoverFront,
-            description: String::new(),
-            data: meta.cover_data.clone(),
-        };
-        tag.add_frame(pic);
-    }
+        if config.embed_lyrics {
+            if let Some(lyrics) = &meta.untimed_lyrics {
+                set_vorbis(&mut tag, "UNSYNCEDLYRICS", lyrics);
+            }
 
-    if let Some(genre) = &meta.genre {
-        tag.set_genre(genre);
-    }
+            if let Some(lyrics) = &meta.timed_lyrics {
+                set_vorbis(&mut tag, "LYRICS", lyrics);
+            }
+        }
 
-    if let Some(year) = meta.year {
-        tag.set_year(year as i32);
+        tag.save()?;
+        Ok(())
     }
+}
 
-    if let Some(lyrics) = &meta.untimed_lyrics {
-        tag.set_text("USLT", lyrics);
-    }
+impl Tagger for Mp3Tagger {
+    fn write(&self, track_path: &PathBuf, meta: &ParsedAlbumMeta, config: &Config) -> Result<(), Box<dyn Error>> {
+        let mut tag = Mp3Tag::new();
+
+        tag.set_album(&meta.album_title);
+        tag.set_album_artist(&meta.album_artist);
+        tag.set_artist(&meta.artist);
+
+        tag.set_title(&meta.title);
+        tag.set_track(meta.track_num as u32);
+        tag.set_total_tracks(meta.track_total as u32);
+        tag.set_disc(meta.disc_num as u32);
+        tag.set_total_discs(meta.disc_total as u32);
+        tag.set_text("TSOP", &meta.artist_sort);
+        tag.set_text("TSO2", &meta.album_artist_sort);
+
+        if !meta.cover_data.is_empty() {
+            let pic = Mp3Image {
+                mime_type: "image/jpeg".to_string(),
+                picture_type: MP3CoverFront,
+                description: String::new(),
+                data: meta.cover_data.clone(),
+            };
+            tag.add_frame(pic);
+        }
 
-    if let Some(lyrics) = &meta.timed_lyrics {
-        tag.set_text("SYLT", lyrics);
-    }
+        if let Some(genre) = &meta.genre {
+            tag.set_genre(genre);
+        }
 
+        if let Some(year) = meta.year {
+            tag.set_year(year as i32);
+        }
 
-    tag.write_to_path(track_path, Version::Id3v24)?;
-    Ok(())
-}
+        if let Some(composer) = &meta.composer {
+            tag.set_text("TCOM", composer);
+        }
 
-fn write_mp4_tags(track_path: &PathBuf, meta: &ParsedAlbumMeta) -> Result<(), MP4Error> {
-    let mut tag = Mp4Tag::read_from_path(&track_path)?;
+        if let Some(isrc) = &meta.isrc {
+            tag.set_text("TSRC", isrc);
+        }
 
-    tag.set_album(&meta.album_title);
-    tag.set_album_artist(&meta.album_artist);
-    tag.set_artist(&meta.artist);
-    tag.set_title(&meta.title);
-    tag.set_track(meta.track_num, meta.track_total);
+        if let Some(copyright) = &meta.copyright {
+            tag.set_text("TCOP", copyright);
+        }
 
-    let covr = Fourcc(*b"covr");
-    if !meta.cover_data.is_empty() {
-        tag.add_data(covr, Mp4Data::Jpeg(meta.cover_data.clone()));
-    }
+        if let Some(release_date) = &meta.release_date {
+            tag.set_text("TDRC", release_date);
+        }
 
-    if let Some(genre) = &meta.genre {
-        tag.set_genre(genre);
-    }
+        // USLT is meant to hold plain, readable lyrics - never the raw [mm:ss.xx]-tagged LRC
+        // text. If the timed lyrics fail to parse into any synced lines, fall back to the
+        // (already plain) untimed lyrics instead of writing the unparsed LRC into USLT.
+        if config.embed_lyrics {
+            if let Some(lyrics) = &meta.timed_lyrics {
+                let lines = lrc::parse(lyrics).unwrap_or_default();
+                if !lines.is_empty() {
+                    let content = lines.iter().map(|l| (l.offset_ms, l.text.clone())).collect();
+                    tag.add_frame(SynchronisedLyrics {
+                        lang: "eng".to_string(),
+                        timestamp_format: TimestampFormat::Ms,
+                        content_type: SynchronisedLyricsType::Lyrics,
+                        description: String::new(),
+                        content,
+                    });
+                    tag.add_frame(Mp3Lyrics {
+                        lang: "eng".to_string(),
+                        description: String::new(),
+                        text: lrc::strip_timestamps(&lines),
+                    });
+                } else if let Some(untimed) = &meta.untimed_lyrics {
+                    tag.add_frame(Mp3Lyrics {
+                        lang: "eng".to_string(),
+                        description: String::new(),
+                        text: untimed.clone(),
+                    });
+                }
+            } else if let Some(lyrics) = &meta.untimed_lyrics {
+                tag.add_frame(Mp3Lyrics {
+                    lang: "eng".to_string(),
+                    description: String::new(),
+                    text: lyrics.clone(),
+                });
+            }
+        }
 
-    if let Some(year) = meta.year {
-        tag.set_year(year.to_string());
+        tag.write_to_path(track_path, Version::Id3v24)?;
+        Ok(())
     }
+}
+
+impl Tagger for Mp4Tagger {
+    fn write(&self, track_path: &PathBuf, meta: &ParsedAlbumMeta, config: &Config) -> Result<(), Box<dyn Error>> {
+        let mut tag = Mp4Tag::read_from_path(&track_path)?;
+
+        tag.set_album(&meta.album_title);
+        tag.set_album_artist(&meta.album_artist);
+        tag.set_artist(&meta.artist);
+        tag.set_title(&meta.title);
+        tag.set_track(meta.track_num, meta.track_total);
+        tag.set_disc(meta.disc_num, meta.disc_total);
+
+        let soar = Fourcc(*b"soar");
+        tag.set_data(soar, Mp4Data::Utf8(meta.artist_sort.clone()));
+        let soaa = Fourcc(*b"soaa");
+        tag.set_data(soaa, Mp4Data::Utf8(meta.album_artist_sort.clone()));
+
+        let covr = Fourcc(*b"covr");
+        if !meta.cover_data.is_empty() {
+            tag.add_data(covr, Mp4Data::Jpeg(meta.cover_data.clone()));
+        }
+
+        if let Some(genre) = &meta.genre {
+            tag.set_genre(genre);
+        }
+
+        if let Some(year) = meta.year {
+            tag.set_year(year.to_string());
+        }
+
+        if let Some(composer) = &meta.composer {
+            tag.set_composer(composer);
+        }
+
+        if let Some(isrc) = &meta.isrc {
+            let isrc_ident = Ident::Freeform { mean: "com.apple.iTunes".to_string(), name: "ISRC".to_string() };
+            tag.set_data(isrc_ident, Mp4Data::Utf8(isrc.clone()));
+        }
+
+        // "©est" has no iTunes-standard meaning; mp4ameta doesn't expose an explicit-rating
+        // atom, so this mirrors the advisory data other taggers pack into a freeform atom.
+        if meta.explicit {
+            let explicit_ident = Fourcc(*b"\xa9est");
+            tag.set_data(explicit_ident, Mp4Data::Utf8("Explicit".to_string()));
+        }
+
+        if config.embed_lyrics {
+            if let Some(lyrics) = &meta.timed_lyrics {
+                match lrc::parse(lyrics) {
+                    Ok(lines) if !lines.is_empty() => tag.set_lyrics(lrc::strip_timestamps(&lines)),
+                    _ => tag.set_lyrics(lyrics),
+                }
+            } else if let Some(lyrics) = &meta.untimed_lyrics {
+                tag.set_lyrics(lyrics);
+            }
+        }
 
-    if let Some(lyrics) = &meta.timed_lyrics {
-        tag.set_lyrics(lyrics);
-    } else if let Some(lyrics) = &meta.untimed_lyrics {
-        tag.set_lyrics(lyrics);
+        tag.write_to_path(&track_path)?;
+        Ok(())
     }
+}
 
-    tag.write_to_path(&track_path)?;
-    Ok(())
+fn write_tags(track_path: &PathBuf, codec: &str, meta: &ParsedAlbumMeta, config: &Config) -> Result<(), Box<dyn Error>> {
+    let tagger: Box<dyn Tagger> = match codec {
+        "flac-mp4" => Box::new(FlacTagger),
+        "mp3-mp4" => Box::new(Mp3Tagger),
+        "aac-mp4" | "he-aac-mp4" => Box::new(Mp4Tagger),
+        _ => return Ok(()),
+    };
+
+    tagger.write(track_path, meta, config)
 }
 
-fn write_tags(track_path: &PathBuf, codec: &str, meta: &ParsedAlbumMeta) -> Result<(), Box<dyn Error>> {
-    match codec {
-        "flac-mp4" => write_flac_tags(track_path, meta)?,
-        "mp3-mp4" => write_mp3_tags(track_path, meta)?,
-        "aac-mp4" | "he-aac-mp4" => write_mp4_tags(track_path, meta)?,
-        _ => {},
+// Re-opens a just-tagged file with the relevant crate and confirms the core fields actually
+// round-tripped, so a truncated or half-written container doesn't get left behind looking done.
+fn verify_tags(track_path: &PathBuf, codec: &str, meta: &ParsedAlbumMeta, config: &Config) -> Result<(), Box<dyn Error>> {
+    let (title, artist, track_num, has_cover): (String, String, u32, bool) = match codec {
+        "flac-mp4" => {
+            let tag = FlacTag::read_from_path(track_path)?;
+            let comments = tag.vorbis_comments().ok_or("flac file has no Vorbis comment block")?;
+            let get = |k: &str| comments.get(k).and_then(|v| v.first()).cloned().unwrap_or_default();
+            (get("TITLE"), get("ARTIST"), get("TRACKNUMBER").parse().unwrap_or(0), tag.pictures().next().is_some())
+        },
+        "mp3-mp4" => {
+            let tag = Mp3Tag::read_from_path(track_path)?;
+            (
+                tag.title().unwrap_or_default().to_string(),
+                tag.artist().unwrap_or_default().to_string(),
+                tag.track().unwrap_or(0),
+                tag.pictures().next().is_some(),
+            )
+        },
+        "aac-mp4" | "he-aac-mp4" => {
+            let tag = Mp4Tag::read_from_path(track_path)?;
+            (
+                tag.title().unwrap_or_default().to_string(),
+                tag.artist().unwrap_or_default().to_string(),
+                tag.track_number().unwrap_or(0) as u32,
+                tag.images().next().is_some(),
+            )
+        },
+        _ => return Ok(()),
+    };
+
+    if title.is_empty() {
+        return Err("verification failed: title tag is missing".into());
+    }
+    if artist.is_empty() {
+        return Err("verification failed: artist tag is missing".into());
+    }
+    if track_num != meta.track_num as u32 {
+        return Err("verification failed: track number tag doesn't match".into());
     }
+    if config.write_covers && !meta.cover_data.is_empty() && !has_cover {
+        return Err("verification failed: cover is missing".into());
+    }
+
     Ok(())
 }
 
-fn write_timed_lyrics(text: &str, out_path: &PathBuf) -> Result<(), Box<dyn Error>> {
+// Re-serializes the fetched LRC text through `lrc::parse`/`lrc::build` so the sidecar
+// carries proper [ti:]/[ar:]/[al:] header tags instead of whatever the API sent verbatim.
+fn write_timed_lyrics(text: &str, meta: &ParsedAlbumMeta, out_path: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let lines = lrc::parse(text)?;
+    let rendered = lrc::build(meta, &lines);
+
     let mut f = File::create(out_path)?;
-    write!(f, "{}", text)?;
+    write!(f, "{}", rendered)?;
     Ok(())
 }
 
+// Sanitises each placeholder's value before substitution, rather than the fully-expanded
+// template, so a literal `/` in the template itself still works as a directory separator
+// (letting templates like "{album_artist}/{year} - {album_title}" lay tracks out in
+// subfolders) while a `/` (or other invalid character) coming from the metadata itself -
+// e.g. an artist name - still gets sanitised away like any other field.
 fn parse_template(template: &str, replacements: HashMap<&str, String>) -> Result<String, RegexError> {
     let mut result = template.to_string();
 
     for (key, value) in replacements {
+        let sanitised_value = utils::sanitise(&value, false)?;
         let to_replace = format!("{{{}}}", key);
-        result = result.replace(&to_replace, &value);
+        result = result.replace(&to_replace, &sanitised_value);
     }
 
-    utils::sanitise(&result, false)
+    Ok(result)
 }
 
 fn parse_album_template(template: &str, meta: &ParsedAlbumMeta) ->  Result<String, RegexError> {
     let m: HashMap<&str, String> = HashMap::from([
         ("album_artist", meta.album_artist.clone()),
         ("album_title", meta.album_title.clone()),
+        ("artist", meta.artist.clone()),
         ("label", meta.label.clone()),
+        ("genre", meta.genre.clone().unwrap_or_default()),
         ("year", meta.year.map(|y| y.to_string()).unwrap_or_default()),
     ]);
 
@@ -462,8 +903,16 @@ fn parse_track_template(template: &str, meta: &ParsedAlbumMeta, padding: String)
     let m: HashMap<&str, String> = HashMap::from([
         ("track_num", meta.track_num.to_string()),
         ("track_num_pad", padding.to_string()),
+        ("track_total", meta.track_total.to_string()),
+        ("disc_num", meta.disc_num.to_string()),
+        ("disc_num_pad", utils::format_track_number(meta.disc_num, meta.disc_total)),
+        ("explicit", if meta.explicit { "Explicit".to_string() } else { String::new() }),
         ("title", meta.title.clone()),
         ("artist", meta.artist.clone()),
+        ("album_artist", meta.album_artist.clone()),
+        ("album_title", meta.album_title.clone()),
+        ("genre", meta.genre.clone().unwrap_or_default()),
+        ("year", meta.year.map(|y| y.to_string()).unwrap_or_default()),
     ]);
 
     let result = parse_template(template, m)?;
@@ -503,8 +952,22 @@ fn mux(in_path: &PathBuf, out_path: &PathBuf, ffmpeg_path: &PathBuf ) -> Result<
     Ok(())
 }
 
-fn process_track(c: &mut YandexMusicClient, track_id: &str, meta: &mut ParsedAlbumMeta, config: &Config, album_path: &PathBuf) -> Result<(), Box<dyn Error>> {
-    let info = c.get_file_info(track_id, &config.format_str)?;
+fn process_track(c: &mut YandexMusicClient, track_id: &str, meta: &mut ParsedAlbumMeta, config: &Config, album_path: &PathBuf, multi: &MultiProgress) -> Result<(), Box<dyn Error>> {
+    let track_id_typed = TrackId::new(track_id)?;
+    let mut info = None;
+    for fmt in &config.quality_chain {
+        match c.get_file_info(&track_id_typed, fmt) {
+            Ok(i) => {
+                println!("Obtained quality tier: {}", fmt);
+                info = Some(i);
+                break;
+            },
+            Err(e) if matches!(e.downcast_ref::<ClientError>(), Some(ClientError::Captcha)) => return Err(e),
+            Err(_) => continue,
+        }
+    }
+    let info = info.ok_or("none of the configured quality tiers were available for this track")?;
+
     let (specs, file_ext) = parse_specs(&info.codec, info.bitrate)
         .ok_or(format!("the api returned an unknown codec: {}", info.codec))?;
 
@@ -514,12 +977,26 @@ fn process_track(c: &mut YandexMusicClient, track_id: &str, meta: &mut ParsedAlb
         println!("Track {} of {}: {} - {}", meta.track_num, meta.track_total, meta.title, specs);
     }
 
-    let padding = utils::format_track_number(meta.track_num, meta.track_total);
-    let san_track_fname = parse_track_template(&config.track_template, &meta, padding.clone())?;
+    if !config.force && config.manifest.lock().unwrap().is_up_to_date(track_id, &info.codec) {
+        println!("Track already in manifest at an equal-or-better quality.");
+        return Ok(());
+    }
+
+    let padding = utils::format_track_number(meta.track_num, meta.disc_track_total);
+    let mut san_track_fname = parse_track_template(&config.track_template, &meta, padding.clone())?;
+    if config.explicit_suffix && meta.explicit {
+        san_track_fname.push_str(" [Explicit]");
+    }
 
     let mut track_path_no_ext = album_path.join(san_track_fname);
     let mut track_path = utils::append_to_path_buf(&track_path_no_ext, &file_ext);
 
+    // track_template may contain its own "/" separators (e.g. "{artist}/{title}"), nesting
+    // the track below album_path, so make sure that subfolder actually exists.
+    if let Some(parent) = track_path_no_ext.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
     match utils::file_exists(&track_path) {
         Ok(true) => {
             println!("Track already exists locally.");
@@ -534,34 +1011,68 @@ fn process_track(c: &mut YandexMusicClient, track_id: &str, meta: &mut ParsedAlb
         Err(err) => return Err(err.into()),
     }
 
-    let track_path_incomp = utils::append_to_path_buf(&track_path_no_ext, ".incomplete");
-    let track_path_incomp_dec = utils::append_to_path_buf(&track_path_no_ext, ".incomplete_dec.mp4");
-    download_track(c, &info.url, &track_path_incomp)?;
+    if config.lyrics_only {
+        if !utils::file_exists(&track_path)? {
+            println!("Track not downloaded locally; skipping lyrics-only fetch.");
+            return Ok(());
+        }
+    }
+
+    fetch_and_write_lyrics(c, track_id, meta, config, &track_path_no_ext)?;
 
-    println!("Decrypting...");
-    decrypt_track(&track_path_incomp, &track_path_incomp_dec, &info.key)?;
+    if config.musicbrainz {
+        if config.mb_client.lock().unwrap().is_none() {
+            *config.mb_client.lock().unwrap() = Some(musicbrainz::MusicBrainzClient::new(&config.out_path)?);
+        }
+        if let Some(mb_client) = config.mb_client.lock().unwrap().as_mut() {
+            if let Err(e) = mb_client.enrich(meta) {
+                println!("MusicBrainz lookup failed.\n{:?}", e);
+            }
+        }
+    }
+
+    if !config.lyrics_only {
+        let track_path_incomp = utils::append_to_path_buf(&track_path_no_ext, ".incomplete");
+        let track_path_incomp_dec = utils::append_to_path_buf(&track_path_no_ext, ".incomplete_dec.mp4");
 
-    println!("Muxing...");
-    mux(&track_path_incomp_dec, &track_path, &config.ffmpeg_path)?;
+        let mut attempt = 0;
+        loop {
+            download_track(c, &info.url, &track_path_incomp, multi)?;
 
-    fs::remove_file(track_path_incomp)?;
-    fs::remove_file(track_path_incomp_dec)?;
+            println!("Decrypting...");
+            decrypt_track(&track_path_incomp, &track_path_incomp_dec, &info.key)?;
 
-    if let Some(lyrics) = meta.lyrics_avail {
-        let lyrics_text = get_lyrics_text(c, track_id, lyrics)?;
-        if lyrics {
-            if config.write_lyrics {
-                let lyrics_path = utils::append_to_path_buf(&track_path_no_ext, ".lrc");
-                println!("Writing timed lyrics file...");
-                write_timed_lyrics(&lyrics_text, &lyrics_path)?;
+            println!("Muxing...");
+            if config.use_ffmpeg {
+                mux(&track_path_incomp_dec, &track_path, &config.ffmpeg_path)?;
+            } else {
+                mp4demux::remux(&track_path_incomp_dec, &track_path, &info.codec)?;
+            }
+
+            fs::remove_file(&track_path_incomp)?;
+            fs::remove_file(&track_path_incomp_dec)?;
+
+            write_tags(&track_path, &info.codec, &meta, config)?;
+
+            match verify_tags(&track_path, &info.codec, &meta, config) {
+                Ok(()) => break,
+                Err(e) if attempt < config.max_retries => {
+                    attempt += 1;
+                    println!("Verification failed, retrying ({}/{}): {}", attempt, config.max_retries, e);
+                    fs::remove_file(&track_path)?;
+                },
+                Err(e) => {
+                    fs::remove_file(&track_path)?;
+                    return Err(e);
+                },
             }
-            meta.timed_lyrics = Some(lyrics_text);
-        } else {
-            meta.untimed_lyrics = Some(lyrics_text);
         }
+    } else {
+        write_tags(&track_path, &info.codec, &meta, config)?;
     }
 
-    write_tags(&track_path, &info.codec, &meta)?;
+    config.manifest.lock().unwrap().record(track_id, &info.codec, info.bitrate, track_path);
+    config.manifest.lock().unwrap().save()?;
 
     Ok(())
 
@@ -570,7 +1081,7 @@ fn process_track(c: &mut YandexMusicClient, track_id: &str, meta: &mut ParsedAlb
 fn process_album(c: &mut YandexMusicClient, config: &Config, album_id: &str, track_id: &str, artist_path: Option<&PathBuf>) -> Result<(), Box<dyn Error>> {
     let is_track_only = !track_id.is_empty();
 
-    let mut meta = c.get_album_meta(album_id)?;
+    let mut meta = c.get_album_meta(&AlbumId::new(album_id)?)?;
     if !meta.available {
         return Err("album is unavailable".into());
     }
@@ -614,23 +1125,72 @@ fn process_album(c: &mut YandexMusicClient, config: &Config, album_id: &str, tra
 
     }
 
-    for volume in meta.volumes {
+    let disc_total = meta.volumes.len();
+    let mut jobs: Vec<(String, ParsedAlbumMeta, PathBuf)> = Vec::new();
+    let country = c.get_user_info()?.region;
+
+    for (disc_idx, volume) in meta.volumes.into_iter().enumerate() {
+        parsed_meta.disc_num = (disc_idx + 1) as u16;
+        parsed_meta.disc_track_total = volume.len() as u16;
+
+        let disc_path = if config.disc_subfolders && disc_total > 1 {
+            let p = album_path.join(format!("CD{}", parsed_meta.disc_num));
+            fs::create_dir_all(&p)?;
+            p
+        } else {
+            album_path.clone()
+        };
+
         for (mut track_num, track) in volume.iter().enumerate() {
             track_num += 1;
             if !track.available {
                 println!("Track is unavailable.");
                 continue;
             }
+            if !c.is_track_available(track, country.as_deref())? {
+                println!("Track is unavailable in your region.");
+                continue;
+            }
             parse_track_meta(&mut parsed_meta, track, track_num as u16, is_track_only);
-            if let Err(e) = process_track(c, &track.id, &mut parsed_meta, &config, &album_path) {
-                println!("Track failed.\n{:?}", e);
+            if !passes_filters(&config, &parsed_meta) {
+                continue;
             }
+            jobs.push((track.id.clone(), parsed_meta.clone(), disc_path.clone()));
         }
     }
 
+    download_jobs(c, config, jobs);
+
     Ok(())
 }
 
+// Spawns every job up front, each with its own cloned client so `&mut self` calls on
+// YandexMusicClient don't contend across threads, but gates the actual track-download work
+// behind `config.download_sem` so no more than `config.concurrency` tracks download at once,
+// no matter how many albums/URLs are fanned out above this (process_artist_albums and the
+// top-level URL loop spawn freely and rely on this one shared semaphore rather than re-
+// chunking by config.concurrency themselves, which would otherwise multiply at each level).
+fn download_jobs(c: &YandexMusicClient, config: &Config, jobs: Vec<(String, ParsedAlbumMeta, PathBuf)>) {
+    let multi = MultiProgress::new();
+
+    thread::scope(|scope| {
+        for (track_id, meta, out_path) in &jobs {
+            let mut worker_client = c.clone();
+            let mut worker_meta = meta.clone();
+            let multi = multi.clone();
+            scope.spawn(move || {
+                let _permit = config.download_sem.acquire();
+                if let Err(e) = process_track(&mut worker_client, track_id, &mut worker_meta, config, out_path, &multi) {
+                    println!("Track failed.\n{:?}", e);
+                }
+                if config.sleep {
+                    thread::sleep(utils::random_sleep_duration(config.sleep_min, config.sleep_max));
+                }
+            });
+        }
+    });
+}
+
 fn select_user_playlist(meta: UserPlaylistsMetaResult, playlist_id: &str) -> Option<UserPlaylist> {
     for tab in meta.tabs.into_iter().filter(|t| t.type_field == "created_playlist_tab") {
         for item in tab.items.into_iter().filter(|i| i.type_field == "liked_playlist_item") {
@@ -660,7 +1220,7 @@ fn process_user_playlist(c: &mut YandexMusicClient, config: &Config, login: &str
             playlist_uuid = playlist.playlist_uuid;
         }
     } else {
-        let playlist = c.get_other_user_playlist_meta(login, playlist_id)?;
+        let playlist = c.get_other_user_playlist_meta(&UserLogin::new(login)?, playlist_id)?;
         if playlist.visibility.to_lowercase() != "public" {
             return Err(
                 "playlist is private and is not owned by the authenticated user".into())
@@ -668,7 +1228,7 @@ fn process_user_playlist(c: &mut YandexMusicClient, config: &Config, login: &str
         playlist_uuid = playlist.playlist_uuid;
     }
 
-    let meta = c.get_playlist_meta(&playlist_uuid)?;
+    let meta = c.get_playlist_meta(&PlaylistId::new(&playlist_uuid)?)?;
     if !meta.available {
         return Err("playlist is unavailable".into());
     }
@@ -682,6 +1242,9 @@ fn process_user_playlist(c: &mut YandexMusicClient, config: &Config, login: &str
 
     let track_total = meta.tracks.len() as u16;
 
+    let mut jobs: Vec<(String, ParsedAlbumMeta, PathBuf)> = Vec::new();
+    let country = c.get_user_info()?.region;
+
     for (mut track_num, t) in meta.tracks.into_iter().enumerate() {
         let track = t.track;
         if track.track_source.to_lowercase() != "own" {
@@ -696,6 +1259,11 @@ fn process_user_playlist(c: &mut YandexMusicClient, config: &Config, login: &str
             continue;
         }
 
+        if !c.is_track_available(&track, country.as_deref())? {
+            println!("Track is unavailable in your region.");
+            continue;
+        }
+
         if !track.albums[0].available {
             println!("Album is unavailable.");
             continue;
@@ -708,16 +1276,19 @@ fn process_user_playlist(c: &mut YandexMusicClient, config: &Config, login: &str
         }
 
         parse_track_meta_playlist(&mut parsed_meta, &track, track_num as u16);
-        if let Err(e) = process_track(c, &track.id, &mut parsed_meta, &config, &plist_path) {
-            println!("Track failed.\n{:?}", e);
+        if !passes_filters(&config, &parsed_meta) {
+            continue;
         }
+        jobs.push((track.id.clone(), parsed_meta, plist_path.clone()));
     }
 
+    download_jobs(c, config, jobs);
+
     Ok(())
 }
 
 fn process_artist_albums(c: &mut YandexMusicClient, config: &Config, artist_id: &str) -> Result<(), Box<dyn Error>> {
-    let meta = c.get_artist_meta(&artist_id)?;
+    let meta = c.get_artist_meta(&ArtistId::new(artist_id)?)?;
     let artist_name = meta.artist.name;
     println!("{}", artist_name);
 
@@ -728,26 +1299,322 @@ fn process_artist_albums(c: &mut YandexMusicClient, config: &Config, artist_id:
         return Err("artist has no albums".into());
     }
 
-    for (mut album_num, album) in meta.albums.iter().enumerate() {
-        album_num += 1;
-        println!("Album {} of {}:", album_num, album_total);
+    // The artist meta endpoint doesn't return track info so just call process_album() per
+    // album, each with its own cloned client. Spawned unconditionally rather than chunked by
+    // config.concurrency - process_album() ends up in download_jobs() for every album, and
+    // download_jobs() is what acquires config.download_sem, so the real per-track work across
+    // every album (and every other concurrently-dispatched URL) still shares one bounded pool
+    // instead of this level multiplying it on top. Holding a permit here too, across the call
+    // into process_album/download_jobs, would deadlock once the pool is exhausted: this thread
+    // would sit in process_album -> download_jobs's own thread::scope waiting on track workers
+    // that can never acquire a permit this thread is still holding.
+    let album_ids: Vec<(usize, u64)> = meta.albums.iter().map(|a| a.id).enumerate().collect();
+
+    thread::scope(|scope| {
+        for &(idx, album_id) in &album_ids {
+            let album_num = idx + 1;
+            let mut worker_client = c.clone();
+            let artist_path = &artist_path;
+            scope.spawn(move || {
+                println!("Album {} of {}:", album_num, album_total);
+                let res = process_album(&mut worker_client, config, &album_id.to_string(), "", Some(artist_path));
+                if let Err(e) = res {
+                    println!("Album failed.\n{:?}", e);
+                }
+                if config.sleep {
+                    thread::sleep(utils::random_sleep_duration(config.sleep_min, config.sleep_max));
+                }
+            });
+        }
+    });
 
-        // The artist meta endpoint doesn't return track info so just call process_album().
-        let res = process_album(c, &config, &album.id.to_string(), &String::new(), Some(&artist_path));
-        if let Err(e) = res {
-            println!("Album failed.\n{:?}", e);
+    Ok(())
+}
+
+// Shared by process_artist_tracks/process_user_liked_tracks: resolves each bare track id to
+// full metadata via get_tracks_meta, then downloads whatever's available into folder_path.
+fn process_track_ids(c: &mut YandexMusicClient, config: &Config, track_ids: &[String], folder_path: &PathBuf) -> Result<(), Box<dyn Error>> {
+    let ids: Vec<TrackId> = track_ids.iter().map(|id| TrackId::new(id)).collect::<Result<Vec<_>, _>>()?;
+    let tracks = c.get_tracks_meta(&ids)?;
+
+    fs::create_dir_all(folder_path)?;
+
+    let track_total = tracks.len() as u16;
+    let mut jobs: Vec<(String, ParsedAlbumMeta, PathBuf)> = Vec::new();
+    let country = c.get_user_info()?.region;
+
+    for (mut track_num, track) in tracks.into_iter().enumerate() {
+        track_num += 1;
+
+        if !track.available {
+            println!("Track is unavailable.");
+            continue;
+        }
+
+        if !c.is_track_available(&track, country.as_deref())? {
+            println!("Track is unavailable in your region.");
+            continue;
+        }
+
+        if track.albums.is_empty() || !track.albums[0].available {
+            println!("Album is unavailable.");
+            continue;
         }
+
+        let mut parsed_meta = parse_album_meta_playlist(&track.albums[0], track_total);
+        if let Some(uri) = &track.cover_uri {
+            let cover_data = get_cover_data(c, uri, config.get_original_covers)?;
+            parsed_meta.cover_data = cover_data;
+        }
+
+        parse_track_meta_trackmeta(&mut parsed_meta, &track, track_num as u16);
+        if !passes_filters(&config, &parsed_meta) {
+            continue;
+        }
+        jobs.push((track.id.clone(), parsed_meta, folder_path.clone()));
     }
 
+    download_jobs(c, config, jobs);
+
     Ok(())
 }
 
+fn process_artist_tracks(c: &mut YandexMusicClient, config: &Config, artist_id: &str) -> Result<(), Box<dyn Error>> {
+    let artist_meta = c.get_artist_meta(&ArtistId::new(artist_id)?)?;
+    let folder_name = format!("{} - Popular tracks", artist_meta.artist.name);
+    println!("{}", folder_name);
+
+    let track_ids = c.get_artist_track_ids(&ArtistId::new(artist_id)?)?;
+    if track_ids.is_empty() {
+        return Err("artist has no tracks".into());
+    }
+
+    let san_folder = utils::sanitise(&folder_name, true)?;
+    let tracks_path = config.out_path.join(san_folder);
+
+    process_track_ids(c, config, &track_ids, &tracks_path)
+}
+
+fn process_user_liked_tracks(c: &mut YandexMusicClient, config: &Config, login: &str) -> Result<(), Box<dyn Error>> {
+    let folder_name = format!("{} - Liked tracks", login);
+    println!("{}", folder_name);
+
+    let track_ids = c.get_liked_track_ids(&UserLogin::new(login)?)?;
+    if track_ids.is_empty() {
+        return Err("user has no liked tracks".into());
+    }
+
+    let san_folder = utils::sanitise(&folder_name, true)?;
+    let tracks_path = config.out_path.join(san_folder);
+
+    process_track_ids(c, config, &track_ids, &tracks_path)
+}
+
 fn compile_regexes() -> Result<Vec<Regex>, regex::Error> {
     REGEX_STRINGS.iter()
         .map(|&s| Regex::new(s))
         .collect()
 }
 
+// Numbered listing: 1..tracks, then albums, then artists, then playlists.
+fn print_search_results(results: &SearchResult) {
+    let mut n = 1;
+
+    if let Some(tracks) = &results.tracks {
+        for t in &tracks.results {
+            println!("{}) Track: {} - {}", n, parse_artists(&t.artists), t.title);
+            n += 1;
+        }
+    }
+    if let Some(albums) = &results.albums {
+        for a in &albums.results {
+            println!("{}) Album: {} - {}", n, parse_artists(&a.artists), a.title);
+            n += 1;
+        }
+    }
+    if let Some(artists) = &results.artists {
+        for a in &artists.results {
+            println!("{}) Artist: {}", n, a.name);
+            n += 1;
+        }
+    }
+    if let Some(playlists) = &results.playlists {
+        for p in &playlists.results {
+            println!("{}) Playlist: {} - {}", n, p.owner.login, p.title);
+            n += 1;
+        }
+    }
+}
+
+fn read_selection(max: usize) -> Result<usize, Box<dyn Error>> {
+    print!("\nEnter a number to download (1-{}): ", max);
+    std::io::stdout().flush()?;
+
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let choice: usize = line.trim().parse()?;
+
+    if choice < 1 || choice > max {
+        return Err("selection out of range".into());
+    }
+    Ok(choice)
+}
+
+fn process_search(c: &mut YandexMusicClient, config: &Config, query: &str) -> Result<(), Box<dyn Error>> {
+    let results = c.search(query, "all", 0)?;
+    print_search_results(&results);
+
+    let tracks = results.tracks.as_ref().map_or(0, |p| p.results.len());
+    let albums = results.albums.as_ref().map_or(0, |p| p.results.len());
+    let artists = results.artists.as_ref().map_or(0, |p| p.results.len());
+    let playlists = results.playlists.as_ref().map_or(0, |p| p.results.len());
+    let total = tracks + albums + artists + playlists;
+
+    if total < 1 {
+        return Err("no results found".into());
+    }
+
+    let choice = read_selection(total)?;
+
+    if choice <= tracks {
+        let track = &results.tracks.unwrap().results[choice - 1];
+        let album_id = track.albums.first()
+            .ok_or("track has no associated album")?
+            .id
+            .to_string();
+        return process_album(c, config, &album_id, &track.id, None);
+    }
+    let choice = choice - tracks;
+
+    if choice <= albums {
+        let album = &results.albums.unwrap().results[choice - 1];
+        return process_album(c, config, &album.id.to_string(), &String::new(), None);
+    }
+    let choice = choice - albums;
+
+    if choice <= artists {
+        let artist = &results.artists.unwrap().results[choice - 1];
+        return process_artist_albums(c, config, &artist.id.to_string());
+    }
+    let choice = choice - artists;
+
+    let playlist = &results.playlists.unwrap().results[choice - 1];
+    process_user_playlist(c, config, &playlist.owner.login, &playlist.kind.to_string())
+}
+
+fn process_track_list(c: &mut YandexMusicClient, config: &Config, folder_name: &str, tracks: Vec<PlaylistTrackItem>) -> Result<(), Box<dyn Error>> {
+    println!("{}", folder_name);
+
+    let san_folder = utils::sanitise(folder_name, true)?;
+    let out_path = config.out_path.join(san_folder);
+    fs::create_dir_all(&out_path)?;
+
+    let track_total = tracks.len() as u16;
+
+    let mut jobs: Vec<(String, ParsedAlbumMeta, PathBuf)> = Vec::new();
+    let country = c.get_user_info()?.region;
+
+    for (mut track_num, t) in tracks.into_iter().enumerate() {
+        let track = t.track;
+        track_num += 1;
+
+        if !track.available {
+            println!("Track is unavailable.");
+            continue;
+        }
+
+        if !c.is_track_available(&track, country.as_deref())? {
+            println!("Track is unavailable in your region.");
+            continue;
+        }
+
+        if !track.albums[0].available {
+            println!("Album is unavailable.");
+            continue;
+        }
+
+        let mut parsed_meta = parse_album_meta_playlist(&track.albums[0], track_total);
+        if let Some(uri) = &track.cover_uri {
+            let cover_data = get_cover_data(c, uri, config.get_original_covers)?;
+            parsed_meta.cover_data = cover_data;
+        }
+
+        parse_track_meta_playlist(&mut parsed_meta, &track, track_num as u16);
+        if !passes_filters(config, &parsed_meta) {
+            continue;
+        }
+        jobs.push((track.id.clone(), parsed_meta, out_path.clone()));
+    }
+
+    download_jobs(c, config, jobs);
+
+    Ok(())
+}
+
+fn process_chart(c: &mut YandexMusicClient, config: &Config, country: &Country) -> Result<(), Box<dyn Error>> {
+    let meta = c.get_chart_meta(country.as_code())?;
+    process_track_list(c, config, &format!("Chart - {:?}", country), meta.tracks)
+}
+
+fn process_new_releases(c: &mut YandexMusicClient, config: &Config) -> Result<(), Box<dyn Error>> {
+    let meta = c.get_new_releases_meta("")?;
+    for album_id in meta.new_releases {
+        if let Err(e) = process_album(c, config, &album_id.to_string(), &String::new(), None) {
+            println!("Album failed.\n{:?}", e);
+        }
+    }
+    Ok(())
+}
+
+fn print_token_report(report: &TokenReport) {
+    for login in &report.valid {
+        println!("Token OK (Plus): {}", login);
+    }
+    for login in &report.non_plus {
+        println!("Token OK but no active Plus subscription, skipped: {}", login);
+    }
+    for masked in &report.invalid {
+        println!("Token invalid, skipped: {}", masked);
+    }
+}
+
+// Dispatches a single URL to the right process_* function and reports the outcome; split out
+// of main()'s loop body so it can run on a worker thread. Sets `stop` rather than returning an
+// error on a captcha, since a worker can't break its siblings out of the surrounding thread scope.
+fn process_url(c: &mut YandexMusicClient, config: &Config, url: &str, comp_regexes: &[Regex], stop: &AtomicBool) {
+    let (first_group, second_group, media_type) = match check_url(url, comp_regexes) {
+        Some((fg, sg, mt)) => (fg, sg, mt),
+        None => {
+            println!("Invalid URL: {}", url);
+            return;
+        }
+    };
+
+    let res = match media_type {
+        // album_id | track_id
+        0 => process_album(c, config, &first_group, &second_group, None),
+        // login | playlist_id
+        1 => process_user_playlist(c, config, &first_group, &second_group),
+        // artist_id
+        2 => process_artist_albums(c, config, &first_group),
+        // artist_id
+        3 => process_artist_tracks(c, config, &first_group),
+        // login
+        4 => process_user_liked_tracks(c, config, &first_group),
+        _ => Ok(()),
+    };
+
+    if let Err(e) = res {
+        if let Some(ClientError::Captcha) = e.downcast_ref::<ClientError>() {
+            println!("{}", e);
+            println!("Stopping early to avoid getting flagged further.");
+            stop.store(true, Ordering::Relaxed);
+            return;
+        }
+        println!("URL failed.\n{:?}", e);
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
     let config = parse_config()
         .expect("failed to parse args/config");
@@ -755,44 +1622,69 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     let comp_regexes = compile_regexes()?;
 
-    let mut c = YandexMusicClient::new(&config.token)?;
+    let mut c = if let Some(path) = &config.token_file {
+        let (client, report) = YandexMusicClient::from_token_file(path)?;
+        print_token_report(&report);
+        client
+    } else {
+        YandexMusicClient::new(&config.token)?
+    };
+    if config.cache || config.clear_cache {
+        c = c.with_cache(utils::get_cache_dir()?, time::Duration::from_secs(config.cache_ttl))?;
+    }
     println!("Signed in successfully.\n");
 
-    let url_total = config.urls.len();
+    if config.clear_cache {
+        c.clear_cache()?;
+        println!("Cache cleared.");
+        return Ok(());
+    }
 
-    for (mut url_num, url) in config.urls.iter().enumerate() {
-        url_num += 1;
-        println!("URL {} of {}:", url_num, url_total);
+    if let Some(query) = &config.search_query {
+        return process_search(&mut c, &config, query);
+    }
 
-        let (first_group, second_group, media_type) = match check_url(url, &comp_regexes) {
-            Some((fg, sg, mt)) => (fg, sg, mt),
-            None => {
-                println!("Invalid URL: {}", url);
-                continue;
-            }
-        };
+    if let Some(country) = &config.charts_country {
+        return process_chart(&mut c, &config, country);
+    }
 
+    if config.new_releases {
+        return process_new_releases(&mut c, &config);
+    }
 
-        let res = match media_type {
-            // album_id | track_id
-            0 => process_album(&mut c, &config, &first_group, &second_group, None),
-            // login | playlist_id
-            1 => process_user_playlist(&mut c, &config, &first_group, &second_group),
-            // artist_id
-            2 => process_artist_albums(&mut c, &config, &first_group),
-            _ => Ok(()),
-        };
+    let url_total = config.urls.len();
+    let stop = AtomicBool::new(false);
+
+    // Every URL is spawned at once rather than chunked by config.concurrency: process_url()
+    // bottoms out in download_jobs() (directly, or via process_artist_albums/process_album)
+    // for its actual per-track work, and download_jobs() is what acquires config.download_sem,
+    // so real concurrent downloads across every URL still share one bounded pool instead of
+    // this level multiplying it on top. Holding a permit here across process_url() would
+    // deadlock the same way it would in process_artist_albums - see the comment there.
+    // `stop` lets any worker that hits a captcha end the run early - other workers check it
+    // before starting their own work instead of being force-joined mid-download.
+    thread::scope(|scope| {
+        for (idx, url) in config.urls.iter().enumerate() {
+            let url_num = idx + 1;
+            let mut worker_client = c.clone();
+            let comp_regexes = &comp_regexes;
+            let config = &config;
+            let stop = &stop;
+            scope.spawn(move || {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
 
-        if let Err(e) = res {
-            println!("URL failed.\n{:?}", e);
-        }
+                println!("URL {} of {}:", url_num, url_total);
+                process_url(&mut worker_client, config, url, comp_regexes, stop);
 
-        if config.sleep {
-            println!("Sleeping...");
-            thread::sleep(time::Duration::from_secs(2));
+                if config.sleep {
+                    println!("Sleeping...");
+                    thread::sleep(utils::random_sleep_duration(config.sleep_min, config.sleep_max));
+                }
+            });
         }
-
-    }
+    });
 
     Ok(())
 }
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+const MANIFEST_FILENAME: &str = "manifest.json";
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ManifestEntry {
+    pub codec: String,
+    pub bitrate: u16,
+    pub path: PathBuf,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+pub struct Manifest {
+    #[serde(default)]
+    entries: HashMap<String, ManifestEntry>,
+    #[serde(skip)]
+    manifest_path: PathBuf,
+}
+
+fn codec_rank(codec: &str) -> u8 {
+    match codec {
+        "flac-mp4" => 3,
+        "aac-mp4" => 2,
+        "he-aac-mp4" => 1,
+        "mp3-mp4" => 1,
+        _ => 0,
+    }
+}
+
+impl Manifest {
+    pub fn load(out_path: &Path) -> Result<Manifest, Box<dyn Error>> {
+        let manifest_path = out_path.join(MANIFEST_FILENAME);
+
+        let mut manifest: Manifest = if manifest_path.exists() {
+            let data = fs::read_to_string(&manifest_path)?;
+            serde_json::from_str(&data)?
+        } else {
+            Manifest::default()
+        };
+
+        manifest.manifest_path = manifest_path;
+        Ok(manifest)
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(&self.manifest_path, data)?;
+        Ok(())
+    }
+
+    // Already downloaded at an equal-or-better quality than what's being requested.
+    pub fn is_up_to_date(&self, track_id: &str, codec: &str) -> bool {
+        match self.entries.get(track_id) {
+            Some(entry) => codec_rank(&entry.codec) >= codec_rank(codec),
+            None => false,
+        }
+    }
+
+    pub fn record(&mut self, track_id: &str, codec: &str, bitrate: u16, path: PathBuf) {
+        self.entries.insert(track_id.to_string(), ManifestEntry {
+            codec: codec.to_string(),
+            bitrate,
+            path,
+        });
+    }
+}
@@ -0,0 +1,301 @@
+// A minimal ISO BMFF (MP4) box walker used to remux a decrypted track without shelling out
+// to ffmpeg. Only understands the boxes needed to locate the single audio track's sample
+// table (stsd/stsz/stco/co64/stsc) and, for FLAC-in-MP4, the `dfLa` box carrying the native
+// STREAMINFO block - just enough to turn the container back into an elementary stream.
+
+use std::error::Error;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+struct Mp4Box<'a> {
+    kind: [u8; 4],
+    data: &'a [u8],
+}
+
+fn read_u32(d: &[u8], off: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes: [u8; 4] = d.get(off..off + 4)
+        .ok_or("truncated mp4 box")?
+        .try_into()?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn read_u64(d: &[u8], off: usize) -> Result<u64, Box<dyn Error>> {
+    let bytes: [u8; 8] = d.get(off..off + 8)
+        .ok_or("truncated mp4 box")?
+        .try_into()?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+// Splits `data` into its immediate child boxes. Doesn't recurse; callers call this again on
+// a container box's `data` to walk further down the tree.
+fn parse_boxes(data: &[u8]) -> Result<Vec<Mp4Box>, Box<dyn Error>> {
+    let mut boxes = Vec::new();
+    let mut pos = 0usize;
+
+    while pos < data.len() {
+        let size32 = read_u32(data, pos)? as u64;
+        let kind: [u8; 4] = data.get(pos + 4..pos + 8)
+            .ok_or("truncated mp4 box header")?
+            .try_into()?;
+
+        let (header_len, box_len) = if size32 == 1 {
+            (16usize, read_u64(data, pos + 8)?)
+        } else if size32 == 0 {
+            (8usize, (data.len() - pos) as u64)
+        } else {
+            (8usize, size32)
+        };
+
+        let box_end = pos + box_len as usize;
+        if box_len < header_len as u64 || box_end > data.len() {
+            return Err("mp4 box size out of range".into());
+        }
+
+        boxes.push(Mp4Box { kind, data: &data[pos + header_len..box_end] });
+        pos = box_end;
+    }
+
+    Ok(boxes)
+}
+
+fn find_box<'a, 'b>(boxes: &'b [Mp4Box<'a>], kind: &[u8; 4]) -> Option<&'b Mp4Box<'a>> {
+    boxes.iter().find(|b| &b.kind == kind)
+}
+
+enum SampleSizes {
+    Uniform(u32, u32),
+    PerSample(Vec<u32>),
+}
+
+impl SampleSizes {
+    fn count(&self) -> u32 {
+        match self {
+            SampleSizes::Uniform(_, count) => *count,
+            SampleSizes::PerSample(sizes) => sizes.len() as u32,
+        }
+    }
+
+    fn size_of(&self, index: usize) -> u32 {
+        match self {
+            SampleSizes::Uniform(size, _) => *size,
+            SampleSizes::PerSample(sizes) => sizes[index],
+        }
+    }
+}
+
+fn parse_stsz(stsz: &Mp4Box) -> Result<SampleSizes, Box<dyn Error>> {
+    let sample_size = read_u32(stsz.data, 4)?;
+    let sample_count = read_u32(stsz.data, 8)?;
+
+    if sample_size != 0 {
+        return Ok(SampleSizes::Uniform(sample_size, sample_count));
+    }
+
+    let mut sizes = Vec::with_capacity(sample_count as usize);
+    for i in 0..sample_count {
+        sizes.push(read_u32(stsz.data, 12 + i as usize * 4)?);
+    }
+    Ok(SampleSizes::PerSample(sizes))
+}
+
+fn parse_chunk_offsets(stbl: &[Mp4Box]) -> Result<Vec<u64>, Box<dyn Error>> {
+    if let Some(stco) = find_box(stbl, b"stco") {
+        let entry_count = read_u32(stco.data, 4)?;
+        let mut offsets = Vec::with_capacity(entry_count as usize);
+        for i in 0..entry_count {
+            offsets.push(read_u32(stco.data, 8 + i as usize * 4)? as u64);
+        }
+        return Ok(offsets);
+    }
+
+    if let Some(co64) = find_box(stbl, b"co64") {
+        let entry_count = read_u32(co64.data, 4)?;
+        let mut offsets = Vec::with_capacity(entry_count as usize);
+        for i in 0..entry_count {
+            offsets.push(read_u64(co64.data, 8 + i as usize * 8)?);
+        }
+        return Ok(offsets);
+    }
+
+    Err("mp4 track has no chunk offset box (stco/co64)".into())
+}
+
+struct StscEntry {
+    first_chunk: u32,
+    samples_per_chunk: u32,
+}
+
+fn parse_stsc(stsc: &Mp4Box) -> Result<Vec<StscEntry>, Box<dyn Error>> {
+    let entry_count = read_u32(stsc.data, 4)?;
+    let mut entries = Vec::with_capacity(entry_count as usize);
+    for i in 0..entry_count {
+        let base = 8 + i as usize * 12;
+        entries.push(StscEntry {
+            first_chunk: read_u32(stsc.data, base)?,
+            samples_per_chunk: read_u32(stsc.data, base + 4)?,
+        });
+    }
+    Ok(entries)
+}
+
+// Walks stsc/stco/stsz to produce each sample's (offset, size) in the track's decode order.
+fn sample_layout(stbl: &[Mp4Box]) -> Result<Vec<(u64, u32)>, Box<dyn Error>> {
+    let stsz = find_box(stbl, b"stsz").ok_or("mp4 track has no stsz box")?;
+    let sizes = parse_stsz(stsz)?;
+    let chunk_offsets = parse_chunk_offsets(stbl)?;
+    let stsc = find_box(stbl, b"stsc").ok_or("mp4 track has no stsc box")?;
+    let stsc_entries = parse_stsc(stsc)?;
+
+    let mut samples = Vec::with_capacity(sizes.count() as usize);
+    let mut sample_idx = 0usize;
+
+    for (i, entry) in stsc_entries.iter().enumerate() {
+        let last_chunk = if i + 1 < stsc_entries.len() {
+            stsc_entries[i + 1].first_chunk - 1
+        } else {
+            chunk_offsets.len() as u32
+        };
+
+        for chunk in entry.first_chunk..=last_chunk {
+            let mut offset = *chunk_offsets.get(chunk as usize - 1)
+                .ok_or("stsc refers to a chunk past the end of stco/co64")?;
+            for _ in 0..entry.samples_per_chunk {
+                let size = sizes.size_of(sample_idx);
+                samples.push((offset, size));
+                offset += size as u64;
+                sample_idx += 1;
+            }
+        }
+    }
+
+    Ok(samples)
+}
+
+// Descends moov/trak/mdia/minf/stbl for the (assumed single) audio track and returns its
+// stsd and stbl children.
+fn find_audio_stbl<'a, 'b>(top: &'b [Mp4Box<'a>]) -> Result<Vec<Mp4Box<'a>>, Box<dyn Error>> {
+    let moov = find_box(top, b"moov").ok_or("mp4 file has no moov box")?;
+    let moov_children = parse_boxes(moov.data)?;
+
+    for child in &moov_children {
+        if &child.kind != b"trak" {
+            continue;
+        }
+
+        let trak_children = parse_boxes(child.data)?;
+        let mdia = match find_box(&trak_children, b"mdia") {
+            Some(b) => b,
+            None => continue,
+        };
+        let mdia_children = parse_boxes(mdia.data)?;
+        let minf = match find_box(&mdia_children, b"minf") {
+            Some(b) => b,
+            None => continue,
+        };
+        let minf_children = parse_boxes(minf.data)?;
+        let stbl = match find_box(&minf_children, b"stbl") {
+            Some(b) => b,
+            None => continue,
+        };
+        return parse_boxes(stbl.data);
+    }
+
+    Err("mp4 file has no audio track".into())
+}
+
+// Extracts the raw STREAMINFO metadata block (header + 34-byte body) from a `flac` sample
+// entry's `dfLa` box.
+fn find_streaminfo(stbl: &[Mp4Box]) -> Result<Vec<u8>, Box<dyn Error>> {
+    let stsd = find_box(stbl, b"stsd").ok_or("mp4 track has no stsd box")?;
+    // version(1) + flags(3) + entry_count(4), then one sample entry box (e.g. `flac`).
+    let sample_entry = parse_boxes(&stsd.data[8..])?
+        .into_iter()
+        .next()
+        .ok_or("stsd box has no sample entry")?;
+
+    // AudioSampleEntry's fixed fields (reserved/data_reference_index/reserved/channelcount/
+    // samplesize/pre_defined/reserved/samplerate) take up 28 bytes before any extension boxes.
+    let extensions = parse_boxes(&sample_entry.data[28..])?;
+    let dfla = find_box(&extensions, b"dfLa").ok_or("flac sample entry has no dfLa box")?;
+
+    // FLACSpecificBox is a FullBox (version+flags, 4 bytes) wrapping one or more native FLAC
+    // metadata blocks verbatim, starting with STREAMINFO.
+    let block_header = dfla.data.get(4..8).ok_or("truncated dfLa box")?;
+    let block_len = u32::from_be_bytes([0, block_header[1], block_header[2], block_header[3]]) as usize;
+    let block_end = 8 + block_len;
+    let mut block = dfla.data.get(4..block_end).ok_or("truncated STREAMINFO block")?.to_vec();
+
+    // Force the last-metadata-block flag, since we only emit this one block.
+    block[0] |= 0x80;
+    Ok(block)
+}
+
+fn remux_flac(data: &[u8], out_path: &Path) -> Result<(), Box<dyn Error>> {
+    let top = parse_boxes(data)?;
+    if find_box(&top, b"mdat").is_none() {
+        return Err("mp4 file has no mdat box".into());
+    }
+    let stbl = find_audio_stbl(&top)?;
+
+    let streaminfo = find_streaminfo(&stbl)?;
+    // stco/co64 chunk offsets are absolute byte offsets into the file, so `data` can be
+    // indexed directly without locating mdat's own position.
+    let samples = sample_layout(&stbl)?;
+
+    let mut out = fs::File::create(out_path)?;
+    out.write_all(b"fLaC")?;
+    out.write_all(&streaminfo)?;
+
+    for (offset, size) in samples {
+        let start = offset as usize;
+        let end = start + size as usize;
+        let frame = data.get(start..end).ok_or("flac sample offset out of range")?;
+        out.write_all(frame)?;
+    }
+
+    Ok(())
+}
+
+// MP3 frames carry their own sync headers and need no container-level header (unlike FLAC's
+// STREAMINFO), so unwrapping mp3-mp4 is just concatenating the sample table's frames verbatim.
+fn remux_mp3(data: &[u8], out_path: &Path) -> Result<(), Box<dyn Error>> {
+    let top = parse_boxes(data)?;
+    if find_box(&top, b"mdat").is_none() {
+        return Err("mp4 file has no mdat box".into());
+    }
+    let stbl = find_audio_stbl(&top)?;
+    let samples = sample_layout(&stbl)?;
+
+    let mut out = fs::File::create(out_path)?;
+    for (offset, size) in samples {
+        let start = offset as usize;
+        let end = start + size as usize;
+        let frame = data.get(start..end).ok_or("mp3 sample offset out of range")?;
+        out.write_all(frame)?;
+    }
+
+    Ok(())
+}
+
+// Dispatches on the codec returned by get-file-info: flac-mp4 is unwrapped into a native FLAC
+// stream, mp3-mp4 into a raw MP3 stream, aac-mp4/he-aac-mp4 are already valid .m4a files and
+// just need copying into place. Any other codec isn't supported natively; the caller should
+// fall back to ffmpeg.
+pub fn remux(in_path: &Path, out_path: &Path, codec: &str) -> Result<(), Box<dyn Error>> {
+    match codec {
+        "flac-mp4" => {
+            let data = fs::read(in_path)?;
+            remux_flac(&data, out_path)
+        },
+        "mp3-mp4" => {
+            let data = fs::read(in_path)?;
+            remux_mp3(&data, out_path)
+        },
+        "aac-mp4" | "he-aac-mp4" => {
+            fs::copy(in_path, out_path)?;
+            Ok(())
+        },
+        _ => Err(format!("native remux doesn't support codec {:?}; enable use_ffmpeg", codec).into()),
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::{thread, time};
+
+use reqwest::blocking::Client;
+use reqwest::header::USER_AGENT;
+use serde::{Deserialize, Serialize};
+
+use crate::structs::ParsedAlbumMeta;
+
+const BASE_URL: &str = "https://musicbrainz.org/ws/2";
+const USER_AGENT_STRING: &str = "Yandex-Music-Downloader/1.0 ( https://github.com/Sorrow446 )";
+const CACHE_FILENAME: &str = "musicbrainz_cache.json";
+
+#[derive(Deserialize)]
+struct MbArtistCredit {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct MbReleaseGroup {
+    #[serde(rename = "first-release-date")]
+    first_release_date: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct MbRelease {
+    #[serde(default)]
+    label: Option<MbLabelInfo>,
+}
+
+#[derive(Deserialize)]
+struct MbLabelInfoLabel {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct MbLabelInfo {
+    label: Option<MbLabelInfoLabel>,
+}
+
+#[derive(Deserialize)]
+struct MbGenre {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct MbRecording {
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<MbArtistCredit>,
+    #[serde(rename = "release-group")]
+    release_group: Option<MbReleaseGroup>,
+    #[serde(default)]
+    releases: Vec<MbRelease>,
+    // MusicBrainz returns these sorted by vote count descending, so the first entry is its
+    // best guess at the recording's genre.
+    #[serde(default)]
+    genres: Vec<MbGenre>,
+}
+
+#[derive(Deserialize)]
+struct MbSearchResult {
+    #[serde(default)]
+    recordings: Vec<MbRecording>,
+}
+
+#[derive(Deserialize, Serialize, Clone)]
+pub struct CachedEnrichment {
+    pub artist: Option<String>,
+    pub label: Option<String>,
+    pub year: Option<u16>,
+    pub genre: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Default)]
+struct Cache {
+    #[serde(default)]
+    entries: HashMap<String, CachedEnrichment>,
+}
+
+pub struct MusicBrainzClient {
+    c: Client,
+    cache: Cache,
+    cache_path: PathBuf,
+}
+
+fn cache_key(title: &str, album_artist: &str) -> String {
+    format!("{}::{}", title.to_lowercase(), album_artist.to_lowercase())
+}
+
+impl MusicBrainzClient {
+    pub fn new(out_path: &Path) -> Result<MusicBrainzClient, Box<dyn Error>> {
+        let c = Client::new();
+        let cache_path = out_path.join(CACHE_FILENAME);
+
+        let cache: Cache = if cache_path.exists() {
+            let data = fs::read_to_string(&cache_path)?;
+            serde_json::from_str(&data)?
+        } else {
+            Cache::default()
+        };
+
+        Ok(MusicBrainzClient { c, cache, cache_path })
+    }
+
+    fn save_cache(&self) -> Result<(), Box<dyn Error>> {
+        let data = serde_json::to_string_pretty(&self.cache)?;
+        fs::write(&self.cache_path, data)?;
+        Ok(())
+    }
+
+    fn query(&mut self, title: &str, album_artist: &str) -> Result<Option<CachedEnrichment>, Box<dyn Error>> {
+        // MusicBrainz asks for no more than one request per second without a rate-limit-exempt key.
+        thread::sleep(time::Duration::from_secs(1));
+
+        let query = format!("recording:\"{}\" AND artist:\"{}\"", title, album_artist);
+        let params = [
+            ("query", query.as_str()),
+            ("fmt", "json"),
+            ("limit", "1"),
+            ("inc", "release-groups+releases+labels+genres"),
+        ];
+
+        let resp = self.c.get(format!("{}/recording", BASE_URL))
+            .header(USER_AGENT, USER_AGENT_STRING)
+            .query(&params)
+            .send()?;
+
+        resp.error_for_status_ref()?;
+        let result: MbSearchResult = resp.json()?;
+
+        let recording = match result.recordings.into_iter().next() {
+            Some(r) => r,
+            None => return Ok(None),
+        };
+
+        let artist = recording.artist_credit.first().map(|a| a.name.clone());
+        let year = recording.release_group
+            .and_then(|rg| rg.first_release_date)
+            .and_then(|d| d.get(0..4).and_then(|y| y.parse::<u16>().ok()));
+        let label = recording.releases.into_iter()
+            .find_map(|r| r.label.and_then(|l| l.label).map(|l| l.name));
+        let genre = recording.genres.into_iter().next().map(|g| g.name);
+
+        Ok(Some(CachedEnrichment { artist, label, year, genre }))
+    }
+
+    // Fills in genre/year/label/artist on `meta` from MusicBrainz where Yandex's own
+    // metadata left them blank; never overwrites fields Yandex already populated.
+    pub fn enrich(&mut self, meta: &mut ParsedAlbumMeta) -> Result<(), Box<dyn Error>> {
+        let key = cache_key(&meta.title, &meta.album_artist);
+
+        let enrichment = if let Some(cached) = self.cache.entries.get(&key) {
+            cached.clone()
+        } else {
+            let fetched = self.query(&meta.title, &meta.album_artist)?
+                .unwrap_or(CachedEnrichment { artist: None, label: None, year: None, genre: None });
+            self.cache.entries.insert(key, fetched.clone());
+            self.save_cache()?;
+            fetched
+        };
+
+        if meta.artist.trim().is_empty() {
+            if let Some(artist) = enrichment.artist {
+                meta.artist = artist;
+            }
+        }
+        if meta.label.trim().is_empty() {
+            if let Some(label) = enrichment.label {
+                meta.label = label;
+            }
+        }
+        if meta.year.is_none() {
+            meta.year = enrichment.year;
+        }
+        if meta.genre.as_ref().map_or(true, |g| g.trim().is_empty()) {
+            if let Some(genre) = enrichment.genre {
+                meta.genre = Some(genre);
+            }
+        }
+
+        Ok(())
+    }
+}
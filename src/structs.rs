@@ -1,10 +1,83 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
+use std::sync::Mutex;
 use serde::Deserialize;
 
+use crate::manifest::Manifest;
+use crate::musicbrainz::MusicBrainzClient;
+use crate::utils::Semaphore;
+
+fn default_concurrency() -> usize {
+    1
+}
+
+fn default_disc_subfolders() -> bool {
+    true
+}
+
+fn default_sleep_min() -> u64 {
+    1
+}
+
+fn default_sleep_max() -> u64 {
+    2
+}
+
+fn default_max_retries() -> usize {
+    3
+}
+
+fn default_cache_ttl() -> u64 {
+    3600
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Country {
+    Global,
+    Ru,
+    By,
+    Kz,
+    Ua,
+}
+
+impl Country {
+    pub fn as_code(&self) -> &'static str {
+        match self {
+            Country::Global => "",
+            Country::Ru => "ru",
+            Country::By => "by",
+            Country::Kz => "kz",
+            Country::Ua => "ua",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum QualityPreset {
+    LosslessOnly,
+    BestAvailable,
+    Mp3Only,
+}
+
+impl QualityPreset {
+    // Ordered from most to least preferred; process_track walks this list, trying each
+    // quality tier against get_file_info() until one is available for the track.
+    pub fn format_chain(&self) -> &'static [&'static str] {
+        match self {
+            QualityPreset::LosslessOnly => &["lossless"],
+            QualityPreset::BestAvailable => &["lossless", "hq", "nq", "lq"],
+            QualityPreset::Mp3Only => &["hq"],
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(name = "Yandex Music Downloader", version = env!("CARGO_PKG_VERSION"))]
 pub struct Args {
+    #[clap(long, help = "Path to config.toml, overriding the usual discovery order.")]
+    pub config: Option<PathBuf>,
+
     #[clap(short, long, help = "1 = AAC 64, 2 = AAC 192, 3 = AAC 256 / MP3 320, 4 = FLAC.")]
     pub format: Option<u8>,
 
@@ -17,17 +90,74 @@ pub struct Args {
     #[clap(short, long, help = "Output path.")]
     pub out_path: Option<PathBuf>,
 
-    #[clap(short, long, help = "Sleep between each track processing to prevent potential rate-limiting.")]
+    #[clap(short, long, help = "Sleep a randomized interval between each track/URL to prevent potential rate-limiting.")]
     pub sleep: bool,
 
+    #[clap(long, help = "Minimum seconds to sleep when --sleep is set.")]
+    pub sleep_min: Option<u64>,
+
+    #[clap(long, help = "Maximum seconds to sleep when --sleep is set.")]
+    pub sleep_max: Option<u64>,
+
     #[clap(long, help = "Write covers to tracks.")]
     pub write_covers: bool,
 
     #[clap(long, help = "Write timed lyrics when available.")]
     pub write_lyrics: bool,
 
-    #[clap(short, long, num_args = 1.., required = true)]
+    #[clap(long, help = "Embed lyrics into the track's own tags, in addition to any .lrc sidecar.")]
+    pub embed_lyrics: bool,
+
+    #[clap(short, long, num_args = 1.., required_unless_present_any = ["search", "charts", "new", "clear_cache"])]
     pub urls: Vec<String>,
+
+    #[clap(long, help = "Search Yandex Music for tracks/albums/artists/playlists instead of downloading URLs directly.")]
+    pub search: Option<String>,
+
+    #[clap(long, value_enum, help = "Download the weekly chart for a country instead of downloading URLs directly.")]
+    pub charts: Option<Country>,
+
+    #[clap(long, help = "Download the new-releases feed instead of downloading URLs directly.")]
+    pub new: bool,
+
+    #[clap(long, alias = "redownload", help = "Ignore the manifest and redownload tracks already present in it.")]
+    pub force: bool,
+
+    #[clap(long, help = "Skip tracks whose genre matches any of these (case-insensitive).")]
+    pub skip_genre: Vec<String>,
+
+    #[clap(long, help = "Only download tracks by these artists (case-insensitive substring match).")]
+    pub only_artist: Vec<String>,
+
+    #[clap(long, help = "Skip tracks flagged as explicit.")]
+    pub skip_explicit: bool,
+
+    #[clap(long, help = "Enrich sparse/localized tags with a MusicBrainz lookup.")]
+    pub musicbrainz: bool,
+
+    #[clap(long, help = "Number of tracks to download at once.")]
+    pub concurrency: Option<usize>,
+
+    #[clap(long, help = "Only fetch/write lyrics for tracks that are already downloaded.")]
+    pub lyrics_only: bool,
+
+    #[clap(long, value_enum, help = "Quality fallback chain to try in order, overriding --format; e.g. best-available tries lossless, then hq, nq, lq.")]
+    pub quality: Option<QualityPreset>,
+
+    #[clap(long, help = "Times to retry a track if post-download tag verification fails.")]
+    pub max_retries: Option<usize>,
+
+    #[clap(long, help = "Cache album/artist/file-info responses on disk to speed up repeated runs.")]
+    pub cache: bool,
+
+    #[clap(long, help = "How long cached responses stay valid, in seconds.")]
+    pub cache_ttl: Option<u64>,
+
+    #[clap(long, help = "Clear the on-disk response cache, then exit.")]
+    pub clear_cache: bool,
+
+    #[clap(long, help = "Path to a file with one OAuth token per line; the first with an active Plus subscription is used.")]
+    pub token_file: Option<PathBuf>,
 }
 
 #[derive(Deserialize)]
@@ -44,22 +174,111 @@ pub struct Config {
     pub token: String,
     pub track_template: String,
     pub sleep: bool,
+    // Randomized sleep range (in seconds) used whenever `sleep` is on, between URLs and
+    // between track/album batches, instead of a fixed, bot-shaped interval.
+    #[serde(default = "default_sleep_min")]
+    pub sleep_min: u64,
+    #[serde(default = "default_sleep_max")]
+    pub sleep_max: u64,
     #[serde(skip_deserializing)]
     pub urls: Vec<String>,
+    #[serde(skip_deserializing)]
+    pub search_query: Option<String>,
+    #[serde(skip_deserializing)]
+    pub charts_country: Option<Country>,
+    #[serde(skip_deserializing)]
+    pub new_releases: bool,
+    // Behind a Mutex so process_track() can record/save without threading &mut Config
+    // through every caller in the process_* chain, and stays safe across worker threads.
+    #[serde(skip_deserializing)]
+    pub manifest: Mutex<Manifest>,
+    #[serde(skip_deserializing)]
+    pub force: bool,
+    #[serde(skip_deserializing)]
+    pub skip_genre: Vec<String>,
+    #[serde(skip_deserializing)]
+    pub only_artist: Vec<String>,
+    #[serde(skip_deserializing)]
+    pub skip_explicit: bool,
+    #[serde(skip_deserializing)]
+    pub musicbrainz: bool,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    // Acquired once per track in download_jobs() so no more than `concurrency` tracks ever
+    // download at once, regardless of how many albums/URLs are fanned out above it; see
+    // `Semaphore`. Sized to `concurrency` in parse_config() once it's resolved.
+    #[serde(skip_deserializing)]
+    pub download_sem: Semaphore,
+    #[serde(skip_deserializing)]
+    pub lyrics_only: bool,
+    // Preset read from config.toml, overridden by --quality when passed; see quality_chain.
+    #[serde(default)]
+    pub quality: Option<QualityPreset>,
+    // Ordered quality tiers to try per track; defaults to a single-entry chain built from
+    // `format_str` when neither --quality nor config.toml's `quality` is set.
+    #[serde(skip_deserializing)]
+    pub quality_chain: Vec<String>,
+    // Lazily built on first use so the lookup stays off the hot path when disabled.
+    #[serde(skip_deserializing)]
+    pub mb_client: Mutex<Option<MusicBrainzClient>>,
     pub use_ffmpeg_env_var: bool,
+    // Mux with ffmpeg instead of the built-in native MP4 remuxer; off by default so the tool
+    // works with zero external binaries.
+    #[serde(default)]
+    pub use_ffmpeg: bool,
     pub write_covers: bool,
     pub write_lyrics: bool,
+    // Embeds lyrics into the track's own tags (SYLT/USLT, LYRICS comment, (c)lyr atom) in
+    // addition to whatever `write_lyrics` puts in a standalone .lrc sidecar.
+    #[serde(default)]
+    pub embed_lyrics: bool,
+    // Lay multi-disc albums out under a "CD<n>" subfolder per disc instead of flattening
+    // every disc's tracks into the album folder.
+    #[serde(default = "default_disc_subfolders")]
+    pub disc_subfolders: bool,
+    // Appends " [Explicit]" to the track filename for tracks flagged explicit by the API,
+    // on top of the `{explicit}` template token.
+    #[serde(default)]
+    pub explicit_suffix: bool,
+    // How many times to redo a track (download through tag-write) if post-download
+    // verification finds it truncated, untagged, or missing a requested cover.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: usize,
+    // Whether to cache album/artist/file-info responses under the platform cache dir;
+    // see `YandexMusicClient::with_cache`.
+    #[serde(skip_deserializing)]
+    pub cache: bool,
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl: u64,
+    #[serde(skip_deserializing)]
+    pub clear_cache: bool,
+    #[serde(skip_deserializing)]
+    pub token_file: Option<PathBuf>,
 }
 
+#[derive(Clone)]
 pub struct ParsedAlbumMeta {
     pub album_title: String,
     pub album_artist: String,
+    pub album_artist_sort: String,
     pub artist: String,
+    pub artist_sort: String,
+    pub composer: Option<String>,
+    pub copyright: Option<String>,
     pub cover_data: Vec<u8>,
+    pub disc_num: u16,
+    pub disc_total: u16,
+    // Track count on this disc only, used to pad filenames; `track_total` below stays the
+    // album-wide count used in tags.
+    pub disc_track_total: u16,
+    pub explicit: bool,
     pub genre: Option<String>,
+    pub instrumental: bool,
+    pub isrc: Option<String>,
     pub lyrics_avail: Option<bool>,
     pub is_track_only: bool,
     pub label: String,
+    pub release_date: Option<String>,
     pub title: String,
     pub timed_lyrics: Option<String>,
     pub untimed_lyrics: Option<String>,
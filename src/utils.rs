@@ -3,10 +3,64 @@ use std::error::Error;
 use std::fs::File;
 use std::io::{BufRead, BufReader, Error as IoError};
 use std::path::PathBuf;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use regex::{Regex, Error as RegexError};
 
 const SAN_REGEX_STRING: &str = r#"[\/:*?"><|]"#;
 
+// A counting semaphore bounding how many tracks download_jobs() downloads at once. The
+// top-level URL loop and process_artist_albums' per-album fan-out spawn their own worker
+// threads freely rather than re-chunking by `concurrency` independently at each nesting
+// level (which would otherwise multiply the effective parallelism at every level of
+// recursion); they all funnel into download_jobs(), so this one shared semaphore is what
+// actually bounds total concurrent downloads across the whole call tree.
+pub struct Semaphore {
+    permits: Mutex<usize>,
+    cv: Condvar,
+}
+
+// Released automatically when dropped, so a worker that returns early (or panics) can't leak
+// its permit and starve the rest of the pool.
+pub struct SemaphorePermit<'a> {
+    sem: &'a Semaphore,
+}
+
+impl Drop for SemaphorePermit<'_> {
+    fn drop(&mut self) {
+        self.sem.release();
+    }
+}
+
+impl Semaphore {
+    pub fn new(permits: usize) -> Self {
+        Semaphore { permits: Mutex::new(permits), cv: Condvar::new() }
+    }
+
+    pub fn acquire(&self) -> SemaphorePermit {
+        let mut permits = self.permits.lock().unwrap();
+        while *permits == 0 {
+            permits = self.cv.wait(permits).unwrap();
+        }
+        *permits -= 1;
+        SemaphorePermit { sem: self }
+    }
+
+    fn release(&self) {
+        let mut permits = self.permits.lock().unwrap();
+        *permits += 1;
+        self.cv.notify_one();
+    }
+}
+
+impl Default for Semaphore {
+    // Placeholder; parse_config() replaces this with one sized to the resolved concurrency
+    // value right after it's known.
+    fn default() -> Self {
+        Semaphore::new(1)
+    }
+}
+
 pub fn get_exe_path() -> Result<PathBuf, Box<dyn Error>> {
     let exe_path = env::current_exe()?;
     let parent_dir = exe_path.parent()
@@ -15,6 +69,38 @@ pub fn get_exe_path() -> Result<PathBuf, Box<dyn Error>> {
     Ok(exe_path_buf)
 }
 
+// Resolves the OS-standard per-user config directory: %APPDATA%\YandexMusicDownloader on
+// Windows, $XDG_CONFIG_HOME/yandex-music-downloader (or ~/.config/yandex-music-downloader)
+// elsewhere.
+pub fn get_config_dir() -> Result<PathBuf, Box<dyn Error>> {
+    if cfg!(target_os = "windows") {
+        let appdata = env::var("APPDATA")?;
+        return Ok(PathBuf::from(appdata).join("YandexMusicDownloader"));
+    }
+
+    let base = match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(env::var("HOME")?).join(".config"),
+    };
+    Ok(base.join("yandex-music-downloader"))
+}
+
+// Resolves the OS-standard per-user cache directory: %LOCALAPPDATA%\YandexMusicDownloader\cache
+// on Windows, $XDG_CACHE_HOME/yandex-music-downloader (or ~/.cache/yandex-music-downloader)
+// elsewhere. Mirrors `get_config_dir`'s resolution, just rooted at the cache dir instead.
+pub fn get_cache_dir() -> Result<PathBuf, Box<dyn Error>> {
+    if cfg!(target_os = "windows") {
+        let local_appdata = env::var("LOCALAPPDATA")?;
+        return Ok(PathBuf::from(local_appdata).join("YandexMusicDownloader").join("cache"));
+    }
+
+    let base = match env::var("XDG_CACHE_HOME") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => PathBuf::from(env::var("HOME")?).join(".cache"),
+    };
+    Ok(base.join("yandex-music-downloader"))
+}
+
 pub fn get_ffmpeg_path() -> Result<PathBuf, Box<dyn Error>> {
     let p = PathBuf::from("./");
     let exe_path = get_exe_path()?;
@@ -117,4 +203,21 @@ pub fn append_to_path_buf(path: &PathBuf, to_append: &str) -> PathBuf {
     let path_str = path.to_string_lossy();
     let new_path_str = format!("{}{}", path_str, to_append);
     PathBuf::from(new_path_str)
+}
+
+// Picks a pseudo-random point in [min, max] seconds so pacing between requests doesn't look
+// like a bot sleeping a fixed interval every time; mirrors the SystemTime-derived jitter
+// YandexMusicClient's backoff_delay already uses rather than pulling in a `rand` dependency.
+pub fn random_sleep_duration(min: u64, max: u64) -> Duration {
+    if max <= min {
+        return Duration::from_secs(min);
+    }
+
+    let span_ms = (max - min) * 1000;
+    let sub_nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+
+    Duration::from_millis(min * 1000 + sub_nanos % (span_ms + 1))
 }
\ No newline at end of file